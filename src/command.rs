@@ -1,9 +1,25 @@
+pub mod clone;
 pub mod complete;
 pub mod completion;
+pub mod config;
 pub mod create;
+pub mod disk;
 pub mod edit;
+pub mod export;
+pub mod gc;
+pub mod import;
+pub mod inspect;
 pub mod install;
+pub mod ip;
 pub mod ipsw;
+pub mod iso;
 pub mod list;
+pub mod remove;
+pub mod rename;
 pub mod run;
+pub mod snapshot;
+pub mod ssh;
+pub mod ssh_config;
 pub mod stop;
+pub mod template;
+pub mod validate;
@@ -0,0 +1,59 @@
+use std::fs;
+
+use clap::Args;
+use tracing::info;
+
+use crate::command::create::random_mac_address;
+use crate::command::create::random_machine_identifier;
+use crate::config::cloud_init;
+use crate::config::vm_config::Os;
+use crate::config::vm_dir;
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::sparse_file;
+
+#[derive(Args)]
+pub struct CloneVm {
+    #[arg(help = "source vm name")]
+    name: String,
+
+    #[arg(help = "new vm name")]
+    new_name: String,
+}
+
+impl CloneVm {
+    pub fn execute(&self) -> Result<()> {
+        let dir = vm_dir::initialized_vm_dir(&self.name)?;
+        vm_dir::validate_name(&self.new_name)?;
+        let new_dir = vm_dir::vm_dir(&self.new_name);
+        if new_dir.dir.exists() {
+            return Err(VzError::InvalidArgument(format!("vm already exists, name={}", self.new_name)));
+        }
+        dir.ensure_stopped()?;
+        // must load before lock, close config.json fd releases the lock
+        let mut config = dir.load_config()?;
+        // hold lock to prevent vm from starting during copy
+        let _lock = dir.lock()?;
+
+        let (temp_dir, _temp_lock) = vm_dir::create_temp_vm_dir()?;
+        info!("copy disk, from={}, to={}", dir.disk_path.to_string_lossy(), temp_dir.disk_path.to_string_lossy());
+        sparse_file::copy(&dir.disk_path, &temp_dir.disk_path)?;
+        fs::copy(&dir.nvram_path, &temp_dir.nvram_path).or_io_error("failed to copy nvram.bin")?;
+        // vm never completed first boot, new instance-id makes cloud-init of clone run as new instance
+        if dir.seed_path.exists() {
+            cloud_init::copy_seed(&dir.seed_path, &temp_dir.seed_path, &self.new_name)?;
+        }
+
+        config.mac_address = random_mac_address();
+        if let Os::MacOs = config.os {
+            config.machine_identifier = Some(random_machine_identifier());
+        }
+        temp_dir.save_config(&config)?;
+
+        info!("move vm dir, from={}, to={}", temp_dir.dir.to_string_lossy(), new_dir.dir.to_string_lossy());
+        fs::rename(&temp_dir.dir, &new_dir.dir).or_io_error("failed to rename dir")?;
+        info!(name = self.new_name, "vm cloned, config={}", new_dir.config_path.to_string_lossy());
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use clap::Args;
 
+use crate::config::template;
 use crate::config::vm_dir;
 
 #[derive(Args)]
@@ -14,6 +15,10 @@ impl Complete {
             for vm_dir in vm_dir::vm_dirs() {
                 println!("{}\tvm", vm_dir.name());
             }
+        } else if self.name == "template_name" {
+            for name in template::names() {
+                println!("{name}\ttemplate");
+            }
         }
     }
 }
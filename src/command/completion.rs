@@ -6,6 +6,8 @@ use clap_complete::Shell;
 use clap_complete::generate;
 
 use crate::Cli;
+use crate::error::Result;
+use crate::error::VzError;
 
 #[derive(Args)]
 pub struct Completion;
@@ -13,17 +15,27 @@ pub struct Completion;
 const CARGO_PKG_NAME: &str = env!("CARGO_PKG_NAME");
 
 impl Completion {
-    pub fn execute() {
-        let shell = Shell::from_env().expect("unknown shell");
+    pub fn execute() -> Result<()> {
+        let shell = Shell::from_env().ok_or_else(|| VzError::InvalidArgument("unknown shell".to_owned()))?;
         generate(shell, &mut Cli::command(), CARGO_PKG_NAME, &mut io::stdout());
         // only support dynamic vm name completion for fish
         // clap dynamic completion is incomplete, better have shell native file completion
         if matches!(shell, Shell::Fish) {
-            for subcommand in ["run", "stop", "edit", "install"] {
+            for subcommand in [
+                "run", "stop", "ip", "ssh", "rm", "mv", "clone", "export", "edit", "config", "install", "inspect",
+                "validate",
+            ] {
                 println!(
                     r#"complete -c {CARGO_PKG_NAME} -x -n "__fish_seen_subcommand_from {subcommand}" -a "({CARGO_PKG_NAME} _complete vm_name)""#
                 );
             }
+            println!(
+                r#"complete -c {CARGO_PKG_NAME} -x -n "__fish_seen_subcommand_from create" -l template -a "({CARGO_PKG_NAME} _complete template_name)""#
+            );
+            println!(
+                r#"complete -c {CARGO_PKG_NAME} -x -n "__fish_seen_subcommand_from show" -a "({CARGO_PKG_NAME} _complete template_name)""#
+            );
         }
+        Ok(())
     }
 }
@@ -0,0 +1,240 @@
+use clap::Args;
+use clap::Subcommand;
+use serde_json::Value;
+use tracing::info;
+
+use crate::config::validation;
+use crate::config::vm_config::VmConfig;
+use crate::config::vm_dir;
+use crate::config::vm_dir::VmDir;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::json;
+
+#[derive(Args)]
+pub struct Config {
+    #[arg(help = "vm name")]
+    name: String,
+
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    #[command(about = "print config value, or whole config if key is not specified")]
+    Get {
+        #[arg(help = "key path, e.g. cpu, sharing.home")]
+        key: Option<String>,
+    },
+    #[command(about = "set config values, e.g. vz config [name] set sharing.home=~ rosetta=true")]
+    Set {
+        #[arg(help = "key=value pairs", required = true)]
+        pairs: Vec<String>,
+    },
+    #[command(about = "remove config values, e.g. vz config [name] unset sharing.home")]
+    Unset {
+        #[arg(help = "key paths", required = true)]
+        keys: Vec<String>,
+    },
+}
+
+// changing these will break existing vm
+const READ_ONLY_KEYS: [&str; 4] = ["schemaVersion", "os", "hardware_model", "machine_identifier"];
+
+impl Config {
+    pub fn execute(&self) -> Result<()> {
+        let dir = vm_dir::initialized_vm_dir(&self.name)?;
+        // hold lock to prevent vm from starting or concurrent change while updating config
+        let lock = match &self.action {
+            Action::Get { .. } => None,
+            Action::Set { .. } | Action::Unset { .. } => {
+                dir.ensure_stopped()?;
+                Some(dir.lock()?)
+            }
+        };
+        let config = match &lock {
+            Some(lock) => dir.load_config_locked(lock)?,
+            None => dir.load_config()?,
+        };
+        let mut config = json::to_value(&config);
+
+        match &self.action {
+            Action::Get { key } => {
+                let value = match key {
+                    Some(key) => get(&config, key).ok_or_else(|| unknown_key(key))?,
+                    None => &config,
+                };
+                match value {
+                    Value::String(value) => println!("{value}"),
+                    Value::Null | Value::Bool(_) | Value::Number(_) | Value::Array(_) | Value::Object(_) => {
+                        println!("{}", json::to_json_pretty(value));
+                    }
+                }
+                return Ok(());
+            }
+            Action::Set { pairs } => {
+                for pair in pairs {
+                    let (key, value) = pair.split_once('=').ok_or_else(|| {
+                        VzError::InvalidArgument(format!("value must be in key=value format, value={pair}"))
+                    })?;
+                    info!("set config, key={key}, value={value}");
+                    set(&mut config, key, value)?;
+                }
+            }
+            Action::Unset { keys } => {
+                for key in keys {
+                    info!("unset config, key={key}");
+                    unset(&mut config, key)?;
+                }
+            }
+        }
+
+        let config: VmConfig = json::from_value(config)?;
+        validation::check(&config)?;
+        // lock is held for set and unset
+        VmDir::save_config_locked(lock.as_ref().unwrap(), &config)
+    }
+}
+
+// config is at most two levels, e.g. "sharing.home", sharing name may contain "."
+fn get<'a>(config: &'a Value, key: &str) -> Option<&'a Value> {
+    match key.split_once('.') {
+        Some((field, sub_key)) => config.get(field)?.get(sub_key),
+        None => config.get(key),
+    }
+}
+
+fn set(config: &mut Value, key: &str, value: &str) -> Result<()> {
+    check_writable(key)?;
+
+    // try value as json first (e.g. true, 4, ["a"]), then as plain string
+    let mut candidates = vec![];
+    if !matches!(get(config, key), Some(Value::String(_)))
+        && let Ok(value) = serde_json::from_str::<Value>(value)
+    {
+        candidates.push(value);
+    }
+    candidates.push(Value::String(value.to_owned()));
+
+    let mut error = None;
+    for candidate in candidates {
+        let mut updated = config.clone();
+        put(&mut updated, key, candidate)?;
+        match check_type(&updated, key) {
+            Ok(()) => {
+                *config = updated;
+                return Ok(());
+            }
+            Err(err) => error = error.or(Some(err)),
+        }
+    }
+    Err(error.unwrap_or_else(|| unknown_key(key)))
+}
+
+fn unset(config: &mut Value, key: &str) -> Result<()> {
+    check_writable(key)?;
+    let mut updated = config.clone();
+    let removed = match key.split_once('.') {
+        Some((field, sub_key)) => {
+            updated.get_mut(field).and_then(Value::as_object_mut).and_then(|object| object.remove(sub_key))
+        }
+        None => updated.as_object_mut().and_then(|object| object.remove(key)),
+    };
+    if removed.is_none() {
+        return Err(unknown_key(key));
+    }
+    if serde_json::from_value::<VmConfig>(updated.clone()).is_err() {
+        return Err(VzError::InvalidArgument(format!("key is required, key={key}")));
+    }
+    *config = updated;
+    Ok(())
+}
+
+fn put(config: &mut Value, key: &str, value: Value) -> Result<()> {
+    let (object, field) = match key.split_once('.') {
+        Some((field, sub_key)) => (config.get_mut(field).and_then(Value::as_object_mut), sub_key),
+        None => (config.as_object_mut(), key),
+    };
+    let object = object.ok_or_else(|| unknown_key(key))?;
+    object.insert(field.to_owned(), value);
+    Ok(())
+}
+
+// deserialize into VmConfig to check type, and serialize back to detect keys VmConfig doesn't know
+fn check_type(config: &Value, key: &str) -> Result<()> {
+    let typed: VmConfig = serde_json::from_value(config.clone())
+        .map_err(|err| VzError::InvalidArgument(format!("invalid value, key={key}, err={err}")))?;
+    if get(&json::to_value(&typed), key).is_none() {
+        return Err(unknown_key(key));
+    }
+    Ok(())
+}
+
+fn check_writable(key: &str) -> Result<()> {
+    let field = key.split_once('.').map_or(key, |(field, _)| field);
+    if READ_ONLY_KEYS.contains(&field) {
+        return Err(VzError::InvalidArgument(format!("key is read only, key={key}")));
+    }
+    Ok(())
+}
+
+fn unknown_key(key: &str) -> VzError {
+    VzError::InvalidArgument(format!("unknown key, key={key}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_config() -> Value {
+        json::from_json(
+            r#"{
+                "schemaVersion": 1,
+                "os": "linux",
+                "cpu": 1,
+                "ram": 1073741824,
+                "macAddress": "f6:db:b3:ec:f9:3f",
+                "sharing": {}
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn set_values() {
+        let mut config = sample_config();
+        set(&mut config, "cpu", "4").unwrap();
+        set(&mut config, "rosetta", "true").unwrap();
+        set(&mut config, "sharing.home", "~").unwrap();
+        set(&mut config, "sharing.data.1", "123").unwrap();
+        set(&mut config, "macAddress", "123").unwrap();
+
+        assert_eq!(Some(&Value::from(4)), get(&config, "cpu"));
+        assert_eq!(Some(&Value::from(true)), get(&config, "rosetta"));
+        assert_eq!(Some(&Value::from("~")), get(&config, "sharing.home"));
+        assert_eq!(Some(&Value::from("123")), get(&config, "sharing.data.1"));
+        assert_eq!(Some(&Value::from("123")), get(&config, "macAddress"));
+    }
+
+    #[test]
+    fn set_invalid_values() {
+        let mut config = sample_config();
+        assert!(matches!(set(&mut config, "cpu", "abc"), Err(VzError::InvalidArgument(_))), "cpu must be number");
+        assert!(matches!(set(&mut config, "unknown", "1"), Err(VzError::InvalidArgument(_))), "unknown key");
+        assert!(matches!(set(&mut config, "cpu.count", "1"), Err(VzError::InvalidArgument(_))), "unknown sub key");
+        assert!(matches!(set(&mut config, "os", "macOS"), Err(VzError::InvalidArgument(_))), "read only key");
+        assert_eq!(sample_config(), config);
+    }
+
+    #[test]
+    fn unset_values() {
+        let mut config = sample_config();
+        set(&mut config, "sharing.home", "~").unwrap();
+        unset(&mut config, "sharing.home").unwrap();
+        assert_eq!(None, get(&config, "sharing.home"));
+
+        assert!(matches!(unset(&mut config, "cpu"), Err(VzError::InvalidArgument(_))), "cpu is required");
+        assert!(matches!(unset(&mut config, "rosetta"), Err(VzError::InvalidArgument(_))), "rosetta is not set");
+    }
+}
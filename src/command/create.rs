@@ -1,6 +1,8 @@
 use std::cmp::max;
 use std::collections::HashMap;
+use std::env;
 use std::fs;
+use std::fs::File;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::mpsc::channel;
@@ -21,11 +23,25 @@ use objc2_virtualization::VZMacMachineIdentifier;
 use objc2_virtualization::VZMacOSRestoreImage;
 use tracing::info;
 
+use crate::command::disk;
+use crate::command::gc;
+use crate::config::cloud_init;
+use crate::config::global_config;
+use crate::config::migration;
+use crate::config::template;
+use crate::config::template::VmTemplate;
+use crate::config::validation;
 use crate::config::vm_config::Os;
+use crate::config::vm_config::Ssh;
 use crate::config::vm_config::VmConfig;
 use crate::config::vm_dir;
 use crate::config::vm_dir::VmDir;
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::disk_image;
 use crate::util::path::PathExtension as _;
+use crate::util::size;
 use crate::vm::mac_os;
 
 #[derive(Args)]
@@ -33,89 +49,172 @@ pub struct Create {
     #[arg(help = "vm name")]
     name: String,
 
-    #[arg(long, help = "create a linux or macOS vm", default_value = "linux")]
-    os: Os,
+    #[arg(long, help = "create a linux or macOS vm [default: linux]")]
+    os: Option<Os>,
 
-    #[arg(long, help = "cpu count", default_value_t = 1)]
-    cpu: usize,
+    #[arg(long, help = "template name in ~/.config/vz/templates, arguments override template values")]
+    template: Option<String>,
 
-    #[arg(long, help = "ram size in gb", default_value_t = 1)]
-    ram: u64,
+    #[arg(long, help = "cpu count [default: 1]")]
+    cpu: Option<usize>,
 
-    #[arg(long, help = "disk size in gb", default_value_t = 50)]
-    disk: u64,
+    #[arg(long, help = "ram size, e.g. 512M, 4G (1024 based), number without unit is in GiB [default: 1G]", value_parser = size::parse_ram)]
+    ram: Option<u64>,
+
+    #[arg(long, help = "disk size, e.g. 500G (1024 based), 100GB (1000 based), number without unit is in GB [default: 50GB]", value_parser = size::parse_disk)]
+    disk: Option<u64>,
 
     #[arg(long, help = "macOS restore image file, e.g. --ipsw=UniversalMac_14.5_23F79_Restore.ipsw", value_hint = ValueHint::FilePath)]
     ipsw: Option<PathBuf>,
+
+    #[arg(long, help = "linux cloud image (qcow2, vmdk or raw) used as disk, e.g. --image=debian-12-genericcloud-arm64.qcow2", value_hint = ValueHint::FilePath)]
+    image: Option<PathBuf>,
+
+    #[arg(long, help = "ssh public key added to cloud-init seed, e.g. --ssh-key ~/.ssh/id_ed25519.pub", value_hint = ValueHint::FilePath, requires = "image")]
+    ssh_key: Option<PathBuf>,
+
+    #[arg(long, help = "user created by cloud-init [default: current user]", requires = "ssh_key")]
+    user: Option<String>,
 }
 
 impl Create {
-    pub fn execute(&self) {
-        self.validate();
-
+    pub fn execute(&self) -> Result<()> {
         let name = &self.name;
+        vm_dir::validate_name(name)?;
+        gc::check();
         let dir = vm_dir::vm_dir(name);
-        assert!(!dir.initialized(), "vm already exists, name={name}");
+        if dir.initialized() {
+            return Err(VzError::InvalidArgument(format!("vm already exists, name={name}")));
+        }
+
+        // precedence: arguments > template > global config > builtin defaults
+        let template = match &self.template {
+            Some(template) => template::load(template)?,
+            None => VmTemplate::default(),
+        };
+        let os = self.os.clone().or_else(|| template.os.clone()).unwrap_or(Os::Linux);
+        let ipsw = self.validate(&os)?;
+        let defaults = global_config::get();
+        let cpu = self.cpu.or(template.cpu).unwrap_or_else(|| defaults.cpu());
+        let ram = self.ram.or(template.ram).unwrap_or_else(|| defaults.ram());
+        let disk = self.disk.or(template.disk).unwrap_or_else(|| defaults.disk());
 
-        let temp_dir = vm_dir::create_temp_vm_dir();
-        temp_dir.resize(self.disk * 1_000_000_000);
+        let ssh_keys = match &self.ssh_key {
+            Some(path) => {
+                Some(cloud_init::parse_ssh_keys(&fs::read_to_string(path).or_io_error("failed to read ssh key")?)?)
+            }
+            None => None,
+        };
+        let user = self.user.clone().or_else(|| env::var("USER").ok()).unwrap_or_else(|| "vz".to_owned());
 
-        match self.os {
-            Os::Linux => create_linux(&temp_dir, self.cpu, self.ram),
-            Os::MacOs => create_macos(&temp_dir, &self.ipsw.as_ref().unwrap().to_absolute_path(), self.cpu, self.ram),
+        // build and validate config before creating temp dir, so invalid template or arguments leave nothing behind
+        let mut config = match ipsw {
+            None => linux_config(cpu, ram),
+            Some(ipsw) => mac_os_config(&ipsw.to_absolute_path(), cpu, ram)?,
+        };
+        template.apply(&mut config);
+        if let Some(ssh_key_path) = &self.ssh_key {
+            config.ssh = Ssh { user: Some(user.clone()), port: None, identity_file: identity_file(ssh_key_path) };
         }
+        validation::check(&config)?;
+
+        let (temp_dir, _temp_lock) = vm_dir::create_temp_vm_dir()?;
+        match &self.image {
+            Some(image) => import_image(&temp_dir, image, disk)?,
+            None => temp_dir.resize(disk)?,
+        }
+
+        info!("create nvram.bin");
+        match &config.hardware_model {
+            Some(hardware_model) => create_mac_os_nvram(&temp_dir, hardware_model)?,
+            None => create_linux_nvram(&temp_dir)?,
+        }
+
+        if let Some(ssh_keys) = &ssh_keys {
+            info!("create cloud-init seed, user={user}");
+            cloud_init::write_seed(&temp_dir.seed_path, name, &user, ssh_keys)?;
+        }
+
+        info!("create config.json");
+        temp_dir.save_config(&config)?;
 
         let vm_dir = vm_dir::vm_dir(&self.name);
         info!("move vm dir, from={}, to={}", temp_dir.dir.to_string_lossy(), vm_dir.dir.to_string_lossy());
-        fs::rename(&temp_dir.dir, &vm_dir.dir).unwrap_or_else(|err| panic!("failed to rename dir, err={err}"));
+        fs::rename(&temp_dir.dir, &vm_dir.dir).or_io_error("failed to rename dir")?;
         info!(name = self.name, "vm created, config={}", vm_dir.config_path.to_string_lossy());
+        Ok(())
     }
 
-    pub fn validate(&self) {
-        if let Os::MacOs = self.os {
-            match &self.ipsw {
-                Some(path) => {
-                    assert!(path.exists(), "ipsw does not exist, path={}", path.to_string_lossy());
+    // return ipsw path for macOS vm
+    fn validate(&self, os: &Os) -> Result<Option<&PathBuf>> {
+        match os {
+            Os::Linux => {
+                for path in [&self.image, &self.ssh_key].into_iter().flatten() {
+                    if !path.exists() {
+                        return Err(VzError::InvalidArgument(format!(
+                            "file does not exist, path={}",
+                            path.to_string_lossy()
+                        )));
+                    }
                 }
-                None => panic!("ipsw is required for macOS vm"),
+                Ok(None)
+            }
+            Os::MacOs if self.image.is_some() => {
+                Err(VzError::InvalidArgument("--image is only supported for linux vm".to_owned()))
             }
+            Os::MacOs => match &self.ipsw {
+                Some(path) if path.exists() => Ok(Some(path)),
+                Some(path) => {
+                    Err(VzError::InvalidArgument(format!("ipsw does not exist, path={}", path.to_string_lossy())))
+                }
+                None => Err(VzError::InvalidArgument("ipsw is required for macOS vm".to_owned())),
+            },
         }
     }
 }
 
-fn create_linux(dir: &VmDir, cpu: usize, ram: u64) {
-    info!("create nvram.bin");
-    unsafe {
-        VZEFIVariableStore::initCreatingVariableStoreAtURL_options_error(
-            VZEFIVariableStore::alloc(),
-            &dir.nvram_path.to_ns_url(),
-            VZEFIVariableStoreInitializationOptions::empty(),
-        )
-        .unwrap_or_else(|err| panic!("failed to create nvram.bin, err={}", err.localizedDescription()));
+// convert image to disk.img, and grow to disk size, cloud-init grows root partition on first boot
+fn import_image(dir: &VmDir, image_path: &Path, disk: u64) -> Result<()> {
+    let image = File::open(image_path).or_io_error("failed to open image")?;
+    let format = disk_image::detect(&image).ok_or_else(|| {
+        VzError::InvalidArgument(format!(
+            "unknown image format, expect qcow2, vmdk or raw disk with partition table, path={}",
+            image_path.to_string_lossy()
+        ))
+    })?;
+    let image_size = disk_image::virtual_size(&image, &format)?;
+    info!("import image, path={}, format={format}", image_path.to_string_lossy());
+    disk_image::convert_to_raw(image_path, &image, &format, &dir.disk_path)?;
+    if image_size < disk {
+        info!("increase disk size, size={}", size::format_decimal(disk));
+        dir.resize(disk)?;
+        disk::relocate_gpt_backup(&dir.disk_path, false)?;
     }
+    Ok(())
+}
 
-    info!("create config.json");
-    let config = VmConfig {
+fn linux_config(cpu: usize, ram: u64) -> VmConfig {
+    VmConfig {
+        schema_version: migration::SCHEMA_VERSION,
         os: Os::Linux,
         cpu,
-        ram: ram * 1024 * 1024 * 1024,
+        ram,
         mac_address: random_mac_address(),
         sharing: HashMap::new(),
         rosetta: Some(false),
         hardware_model: None,
         machine_identifier: None,
-    };
-    dir.save_config(&config);
+        ssh: Ssh::default(),
+        ports: vec![],
+    }
 }
 
-fn create_macos(dir: &VmDir, ipsw: &Path, cpu: usize, ram: u64) {
-    let image = load_mac_os_restore_image(ipsw);
-
-    let requirements = unsafe {
-        image.mostFeaturefulSupportedConfiguration().expect("restore image is not supported by current host")
-    };
+// cpu, ram and hardware model are from requirements of restore image
+fn mac_os_config(ipsw: &Path, cpu: usize, ram: u64) -> Result<VmConfig> {
+    let image = load_mac_os_restore_image(ipsw)?;
 
-    info!("create nvram.bin");
+    let requirements = unsafe { image.mostFeaturefulSupportedConfiguration() }
+        .ok_or_else(|| VzError::Framework("restore image is not supported by current host".to_owned()))?;
     let hardware_model = unsafe {
         requirements
             .hardwareModel()
@@ -123,50 +222,81 @@ fn create_macos(dir: &VmDir, ipsw: &Path, cpu: usize, ram: u64) {
             .base64EncodedStringWithOptions(NSDataBase64EncodingOptions::empty())
             .to_string()
     };
+    Ok(VmConfig {
+        schema_version: migration::SCHEMA_VERSION,
+        os: Os::MacOs,
+        cpu: max(cpu, unsafe { requirements.minimumSupportedCPUCount() }),
+        ram: max(ram, unsafe { requirements.minimumSupportedMemorySize() }),
+        mac_address: random_mac_address(),
+        sharing: HashMap::new(),
+        rosetta: None,
+        hardware_model: Some(hardware_model),
+        machine_identifier: Some(random_machine_identifier()),
+        ssh: Ssh::default(),
+        ports: vec![],
+    })
+}
+
+fn create_linux_nvram(dir: &VmDir) -> Result<()> {
     unsafe {
-        let model = mac_os::hardware_model(&hardware_model);
+        VZEFIVariableStore::initCreatingVariableStoreAtURL_options_error(
+            VZEFIVariableStore::alloc(),
+            &dir.nvram_path.to_ns_url(),
+            VZEFIVariableStoreInitializationOptions::empty(),
+        )
+        .map_err(|err| VzError::Framework(format!("failed to create nvram.bin, err={}", err.localizedDescription())))?;
+    }
+    Ok(())
+}
+
+fn create_mac_os_nvram(dir: &VmDir, hardware_model: &str) -> Result<()> {
+    unsafe {
+        let model = mac_os::hardware_model(hardware_model);
         VZMacAuxiliaryStorage::initCreatingStorageAtURL_hardwareModel_options_error(
             VZMacAuxiliaryStorage::alloc(),
             &dir.nvram_path.to_ns_url(),
             &model,
             VZMacAuxiliaryStorageInitializationOptions::empty(),
         )
-        .unwrap_or_else(|err| panic!("failed to create nvram.bin, err={}", err.localizedDescription()));
+        .map_err(|err| VzError::Framework(format!("failed to create nvram.bin, err={}", err.localizedDescription())))?;
     }
+    Ok(())
+}
+
+// private key next to public key, e.g. ~/.ssh/id_ed25519.pub => ~/.ssh/id_ed25519
+fn identity_file(ssh_key: &Path) -> Option<String> {
+    if ssh_key.extension().is_none_or(|extension| extension != "pub") {
+        return None;
+    }
+    let identity_file = fs::canonicalize(ssh_key.with_extension("")).ok()?;
+    Some(identity_file.to_string_lossy().to_string())
+}
 
-    info!("create config.json");
-    let machine_identifier = unsafe {
+pub fn random_mac_address() -> String {
+    unsafe { VZMACAddress::randomLocallyAdministeredAddress().string().to_string() }
+}
+
+pub fn random_machine_identifier() -> String {
+    unsafe {
         VZMacMachineIdentifier::new()
             .dataRepresentation()
             .base64EncodedStringWithOptions(NSDataBase64EncodingOptions::empty())
             .to_string()
-    };
-    let config = VmConfig {
-        os: Os::MacOs,
-        cpu: max(cpu, unsafe { requirements.minimumSupportedCPUCount() }),
-        ram: max(ram * 1024 * 1024 * 1024, unsafe { requirements.minimumSupportedMemorySize() }),
-        mac_address: random_mac_address(),
-        sharing: HashMap::new(),
-        rosetta: None,
-        hardware_model: Some(hardware_model),
-        machine_identifier: Some(machine_identifier),
-    };
-    dir.save_config(&config);
-}
-
-fn random_mac_address() -> String {
-    unsafe { VZMACAddress::randomLocallyAdministeredAddress().string().to_string() }
+    }
 }
 
-fn load_mac_os_restore_image(ipsw: &Path) -> Retained<VZMacOSRestoreImage> {
+fn load_mac_os_restore_image(ipsw: &Path) -> Result<Retained<VZMacOSRestoreImage>> {
     let (tx, rx) = channel();
     unsafe {
         let block = StackBlock::new(move |image: *mut VZMacOSRestoreImage, err: *mut NSError| {
-            assert!(err.is_null(), "failed to load image, err={}", (*err).localizedDescription());
-            let image = Retained::from_raw(image).unwrap();
-            tx.send(image).unwrap();
+            let result = if err.is_null() {
+                Ok(Retained::from_raw(image).unwrap())
+            } else {
+                Err(VzError::Framework(format!("failed to load image, err={}", (*err).localizedDescription())))
+            };
+            tx.send(result).unwrap();
         });
         VZMacOSRestoreImage::loadFileURL_completionHandler(&ipsw.to_ns_url(), &block);
     };
-    rx.recv().unwrap()
+    rx.recv().map_err(|err| VzError::Framework(format!("failed to load image, err={err}")))?
 }
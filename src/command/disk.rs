@@ -0,0 +1,282 @@
+use std::fs;
+use std::fs::File;
+use std::os::unix::fs::MetadataExt as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+use clap::Args;
+use clap::Subcommand;
+use clap::ValueEnum;
+use clap::ValueHint;
+use tracing::info;
+use tracing::warn;
+
+use crate::config::vm_dir;
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::disk_image;
+use crate::util::disk_image::ImageFormat;
+use crate::util::gpt;
+use crate::util::qcow2;
+use crate::util::size;
+use crate::util::sparse_file;
+use crate::util::table::print_table;
+
+#[derive(Args)]
+pub struct Disk {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    #[command(about = "show disk size, allocated size and gpt partitions")]
+    Info {
+        #[arg(help = "vm name")]
+        name: String,
+    },
+    #[command(
+        about = "punch holes in zero filled blocks of disk.img, run 'fstrim -a' in guest first to zero freed blocks"
+    )]
+    Compact {
+        #[arg(help = "vm name")]
+        name: String,
+    },
+    #[command(about = "shrink disk.img, partitions must be shrunk in guest first")]
+    Shrink {
+        #[arg(help = "vm name")]
+        name: String,
+        #[arg(help = "disk size, e.g. 500G (1024 based), 100GB (1000 based), number without unit is in GB", value_parser = size::parse_disk)]
+        size: u64,
+    },
+    #[command(about = "replace disk.img with image, format (qcow2, vmdk or raw) is detected from content")]
+    Import {
+        #[arg(help = "vm name")]
+        name: String,
+        #[arg(help = "disk image, e.g. debian-12-genericcloud-arm64.qcow2", value_hint = ValueHint::FilePath)]
+        path: PathBuf,
+        #[arg(
+            long,
+            help = "image format, required if content is not detected, e.g. raw image without partition table"
+        )]
+        format: Option<ImportFormat>,
+    },
+    #[command(about = "export disk.img as image")]
+    Export {
+        #[arg(help = "vm name")]
+        name: String,
+        #[arg(help = "disk image, e.g. debian.qcow2", value_hint = ValueHint::FilePath)]
+        path: PathBuf,
+        #[arg(long, help = "image format", default_value = "qcow2")]
+        format: Format,
+    },
+}
+
+#[derive(Clone, ValueEnum)]
+enum Format {
+    Qcow2,
+    Raw,
+}
+
+#[derive(Clone, ValueEnum)]
+enum ImportFormat {
+    Qcow2,
+    Vmdk,
+    Raw,
+}
+
+impl Disk {
+    pub fn execute(&self) -> Result<()> {
+        match &self.action {
+            Action::Info { name } => info(name),
+            Action::Compact { name } => compact(name),
+            Action::Shrink { name, size } => shrink(name, *size),
+            Action::Import { name, path, format } => import(name, path, format.as_ref()),
+            Action::Export { name, path, format } => export(name, path, format),
+        }
+    }
+}
+
+fn info(name: &str) -> Result<()> {
+    let dir = vm_dir::initialized_vm_dir(name)?;
+    let file = File::open(&dir.disk_path).or_io_error("failed to open disk")?;
+    let disk_size = file.metadata().or_io_error("failed to read disk metadata")?.len();
+    println!("size: {}", size::format_decimal(disk_size));
+    println!("allocated: {}", size::format_decimal(allocated_size(&dir.disk_path)?));
+
+    let Some(gpt) = gpt::read(&file)? else {
+        println!("partition table: none");
+        return Ok(());
+    };
+    println!("disk guid: {}", gpt::format_guid(&gpt.header.disk_guid));
+    println!();
+    let rows: Vec<_> = gpt
+        .partitions
+        .iter()
+        .map(|partition| {
+            [
+                (partition.index + 1).to_string(),
+                partition.name.clone(),
+                gpt::partition_type(&partition.type_guid).to_owned(),
+                partition.first_lba.to_string(),
+                partition.last_lba.to_string(),
+                size::format_decimal(partition.size()),
+            ]
+        })
+        .collect();
+    print_table(["index", "name", "type", "start", "end", "size"], &rows);
+
+    if gpt.header.backup_lba != disk_size / gpt::SECTOR_SIZE - 1 {
+        warn!("backup gpt header is not at end of disk, run 'sgdisk -e' in guest to relocate it");
+    }
+    Ok(())
+}
+
+// move gpt backup header to end of resized disk, skip if disk is not partitioned
+pub fn relocate_gpt_backup(path: &Path, grow_last_partition: bool) -> Result<()> {
+    let file = File::options().read(true).write(true).open(path).or_io_error("failed to open disk")?;
+    let Some(mut gpt) = gpt::read(&file)? else {
+        return Ok(());
+    };
+    let disk_size = file.metadata().or_io_error("failed to read disk metadata")?.len();
+    info!("relocate gpt backup header, file={}", path.to_string_lossy());
+    gpt::relocate_backup(&file, &mut gpt, disk_size, grow_last_partition)?;
+    if grow_last_partition && let Some(partition) = gpt.partitions.iter().max_by_key(|partition| partition.last_lba) {
+        info!("grow last partition, index={}, size={}", partition.index + 1, size::format_decimal(partition.size()));
+    }
+    Ok(())
+}
+
+fn compact(name: &str) -> Result<()> {
+    let dir = vm_dir::initialized_vm_dir(name)?;
+    dir.ensure_stopped()?;
+    let _lock = dir.lock()?;
+
+    let before = allocated_size(&dir.disk_path)?;
+    info!("compact disk, file={}", dir.disk_path.to_string_lossy());
+    sparse_file::punch_zero_blocks(&dir.disk_path)?;
+    let after = allocated_size(&dir.disk_path)?;
+    info!(
+        "disk compacted, before={}, after={}, released={}",
+        size::format_decimal(before),
+        size::format_decimal(after),
+        size::format_decimal(before.saturating_sub(after))
+    );
+    Ok(())
+}
+
+fn shrink(name: &str, size: u64) -> Result<()> {
+    let dir = vm_dir::initialized_vm_dir(name)?;
+    dir.ensure_stopped()?;
+    let _lock = dir.lock()?;
+
+    let file = File::options().read(true).write(true).open(&dir.disk_path).or_io_error("failed to open disk")?;
+    let current_size = file.metadata().or_io_error("failed to read disk metadata")?.len();
+    if size >= current_size {
+        return Err(VzError::InvalidArgument(format!(
+            "disk size must be smaller than current, current={}",
+            size::format_decimal(current_size)
+        )));
+    }
+    if !size.is_multiple_of(gpt::SECTOR_SIZE) {
+        return Err(VzError::InvalidArgument(format!("disk size must be multiple of {} bytes", gpt::SECTOR_SIZE)));
+    }
+
+    let mut gpt = gpt::read(&file)?
+        .ok_or_else(|| VzError::InvalidArgument("gpt not found, not able to verify partitions".to_owned()))?;
+    // keep space for backup partition entries and header after last partition
+    let min_size = gpt.min_disk_size();
+    if size < min_size {
+        return Err(VzError::InvalidArgument(format!(
+            "last partition extends past requested size, min_size={}",
+            size::format_decimal(min_size)
+        )));
+    }
+
+    // write backup gpt at new end before truncating, so disk always has valid backup gpt
+    info!("relocate gpt backup header, file={}", dir.disk_path.to_string_lossy());
+    gpt::relocate_backup(&file, &mut gpt, size, false)?;
+    info!("shrink disk, file={}, size={}", dir.disk_path.to_string_lossy(), size::format_decimal(size));
+    file.set_len(size).or_io_error("failed to resize disk")?;
+    file.sync_all().or_io_error("failed to sync disk")
+}
+
+fn import(name: &str, path: &Path, format: Option<&ImportFormat>) -> Result<()> {
+    let dir = vm_dir::initialized_vm_dir(name)?;
+    dir.ensure_stopped()?;
+    let _lock = dir.lock()?;
+
+    let image = File::open(path).or_io_error("failed to open image")?;
+    // unknown content must not replace disk.img unless format is given explicitly
+    let format = match format {
+        Some(ImportFormat::Qcow2) => ImageFormat::Qcow2,
+        Some(ImportFormat::Vmdk) => ImageFormat::Vmdk,
+        Some(ImportFormat::Raw) => ImageFormat::Raw,
+        None => disk_image::detect(&image).ok_or_else(|| {
+            VzError::InvalidArgument(format!(
+                "unknown image format, use --format raw to import as raw disk, path={}",
+                path.to_string_lossy()
+            ))
+        })?,
+    };
+    let image_size = disk_image::virtual_size(&image, &format)?;
+    let current_size = dir.disk_path.metadata().or_io_error("failed to read disk metadata")?.len();
+
+    // convert to temp file then rename, so disk.img stays intact if conversion fails
+    info!("import disk, path={}, format={format}, size={}", path.to_string_lossy(), size::format_decimal(image_size));
+    let temp_disk_path = dir.dir.join("disk.img.import");
+    if temp_disk_path.exists() {
+        fs::remove_file(&temp_disk_path).or_io_error("failed to remove file")?;
+    }
+    let result = disk_image::convert_to_raw(path, &image, &format, &temp_disk_path).and_then(|()| {
+        // keep disk size if image is smaller, e.g. cloud image grows root partition on first boot
+        if image_size < current_size {
+            let disk = File::options().write(true).open(&temp_disk_path).or_io_error("failed to open disk")?;
+            disk.set_len(current_size).or_io_error("failed to resize disk")?;
+            drop(disk);
+            relocate_gpt_backup(&temp_disk_path, false)?;
+        }
+        fs::rename(&temp_disk_path, &dir.disk_path).or_io_error("failed to rename disk.img")
+    });
+    if result.is_err()
+        && let Err(err) = fs::remove_file(&temp_disk_path)
+    {
+        warn!("failed to remove temp disk, path={}, err={err}", temp_disk_path.to_string_lossy());
+    }
+    result?;
+    let size = dir.disk_path.metadata().or_io_error("failed to read disk metadata")?.len();
+    info!("disk imported, name={name}, size={}", size::format_decimal(size));
+    Ok(())
+}
+
+fn export(name: &str, path: &Path, format: &Format) -> Result<()> {
+    let dir = vm_dir::initialized_vm_dir(name)?;
+    if path.exists() {
+        return Err(VzError::InvalidArgument(format!("file already exists, path={}", path.to_string_lossy())));
+    }
+    dir.ensure_stopped()?;
+    // hold lock to prevent vm from starting during export
+    let _lock = dir.lock()?;
+
+    info!("export disk, name={name}, path={}", path.to_string_lossy());
+    let result = match format {
+        Format::Qcow2 => File::open(&dir.disk_path).or_io_error("failed to open disk").and_then(|disk| {
+            let image = File::create_new(path).or_io_error("failed to create file")?;
+            qcow2::from_raw(&disk, &image)
+        }),
+        Format::Raw => sparse_file::copy(&dir.disk_path, path),
+    };
+    if result.is_err()
+        && let Err(err) = fs::remove_file(path)
+    {
+        warn!("failed to remove image, path={}, err={err}", path.to_string_lossy());
+    }
+    result?;
+    info!("disk exported, path={}", path.to_string_lossy());
+    Ok(())
+}
+
+fn allocated_size(path: &Path) -> Result<u64> {
+    Ok(path.metadata().or_io_error("failed to read disk metadata")?.blocks() * 512)
+}
@@ -1,49 +1,61 @@
 use clap::Args;
 use tracing::info;
 
+use crate::command::disk;
 use crate::config::vm_dir;
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::size;
 
 #[derive(Args)]
 pub struct Edit {
     #[arg(help = "vm name")]
     name: String,
 
-    #[arg(long, help = "disk size in gb")]
+    #[arg(long, help = "disk size, e.g. 500G (1024 based), 100GB (1000 based), number without unit is in GB", value_parser = size::parse_disk)]
     disk: Option<u64>,
 
+    #[arg(long, help = "grow last partition to fill disk after increasing disk size", requires = "disk")]
+    grow_partition: bool,
+
     #[arg(long, help = "cpu count")]
     cpu: Option<usize>,
 
-    #[arg(long, help = "ram size in gb")]
+    #[arg(long, help = "ram size, e.g. 512M, 4G (1024 based), number without unit is in GiB", value_parser = size::parse_ram)]
     ram: Option<u64>,
 }
 
 impl Edit {
-    pub fn execute(&self) {
-        let name = &self.name;
-        let dir = vm_dir::vm_dir(name);
-        assert!(dir.initialized(), "vm not initialized, name={name}");
-
-        assert!(dir.pid().is_none(), "vm is running, name={name}");
+    pub fn execute(&self) -> Result<()> {
+        let dir = vm_dir::initialized_vm_dir(&self.name)?;
+        dir.ensure_stopped()?;
 
         // Check if at least one argument was provided
-        assert!(
-            !(self.disk.is_none() && self.cpu.is_none() && self.ram.is_none()),
-            "at least one of --disk, --cpu, or --ram must be specified"
-        );
+        if self.disk.is_none() && self.cpu.is_none() && self.ram.is_none() {
+            return Err(VzError::InvalidArgument(
+                "at least one of --disk, --cpu, or --ram must be specified".to_owned(),
+            ));
+        }
 
         // Handle disk resize
         if let Some(disk) = self.disk {
-            let size = dir.disk_path.metadata().unwrap_or_else(|err| panic!("failed to get metadata, err={err}")).len();
-            assert!(size < disk * 1_000_000_000, "disk size must be larger than current, current={size}G");
+            let size = dir.disk_path.metadata().or_io_error("failed to get metadata")?.len();
+            if size >= disk {
+                return Err(VzError::InvalidArgument(format!(
+                    "disk size must be larger than current, current={}",
+                    size::format_decimal(size)
+                )));
+            }
 
-            info!("increase disk size, file={}, size={}G", dir.disk_path.to_string_lossy(), disk);
-            dir.resize(disk * 1_000_000_000);
+            info!("increase disk size, file={}, size={}", dir.disk_path.to_string_lossy(), size::format_decimal(disk));
+            dir.resize(disk)?;
+            disk::relocate_gpt_backup(&dir.disk_path, self.grow_partition)?;
         }
 
         // Handle CPU/RAM changes
         if self.cpu.is_some() || self.ram.is_some() {
-            let mut config = dir.load_config();
+            let mut config = dir.load_config()?;
 
             if let Some(cpu) = self.cpu {
                 info!("change cpu count, from={}, to={cpu}", config.cpu);
@@ -51,11 +63,12 @@ impl Edit {
             }
 
             if let Some(ram) = self.ram {
-                info!("change ram size, from={:.2}G, to={ram}G", config.ram as f32 / (1024 * 1024 * 1024) as f32);
-                config.ram = ram * 1024 * 1024 * 1024;
+                info!("change ram size, from={}, to={}", size::format_binary(config.ram), size::format_binary(ram));
+                config.ram = ram;
             }
 
-            dir.save_config(&config);
+            dir.save_config(&config)?;
         }
+        Ok(())
     }
 }
@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::Args;
+use clap::ValueHint;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::info;
+use tracing::warn;
+
+use crate::config::vm_dir;
+use crate::config::vm_dir::VmDir;
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::json;
+use crate::util::tar::TarWriter;
+use crate::util::zstd;
+
+#[derive(Args)]
+pub struct Export {
+    #[arg(help = "vm name")]
+    name: String,
+
+    #[arg(help = "archive file, e.g. debian.tar.zst", value_hint = ValueHint::FilePath)]
+    path: PathBuf,
+}
+
+// manifest.json is last entry of archive, as checksums are calculated while writing
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub name: String,
+    pub schema_version: u64,
+    pub files: BTreeMap<String, ManifestFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ManifestFile {
+    pub size: u64,
+    // sha256 of content stored in archive, for sparse disk.img, it covers sparse map and data regions
+    pub sha256: String,
+}
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+impl Export {
+    pub fn execute(&self) -> Result<()> {
+        let dir = vm_dir::initialized_vm_dir(&self.name)?;
+        if self.path.exists() {
+            return Err(VzError::InvalidArgument(format!("file already exists, path={}", self.path.to_string_lossy())));
+        }
+        dir.ensure_stopped()?;
+        zstd::check()?;
+        // hold lock to prevent vm from starting during export
+        let lock = dir.lock()?;
+        // upgrade config, so archive always carries current schema
+        let config = dir.load_config_locked(&lock)?;
+        let config_json = lock.read().or_io_error("failed to load config")?;
+
+        info!("export vm, name={}, path={}", self.name, self.path.to_string_lossy());
+        let mut child = zstd::compress(&self.path)?;
+        let result = match child.stdin.take() {
+            Some(stdin) => write_archive(&dir, config.schema_version, &config_json, BufWriter::new(stdin)),
+            None => Err(VzError::InvalidArgument("failed to open zstd stdin".to_owned())),
+        };
+        let result = result.and(zstd::wait(child));
+        if result.is_err()
+            && let Err(err) = fs::remove_file(&self.path)
+        {
+            warn!("failed to remove archive, path={}, err={err}", self.path.to_string_lossy());
+        }
+        result?;
+        info!("vm exported, path={}", self.path.to_string_lossy());
+        Ok(())
+    }
+}
+
+// config.json is lock file, it must be read via lock, otherwise lock is released
+fn write_archive(dir: &VmDir, schema_version: u64, config_json: &str, writer: impl Write) -> Result<()> {
+    let mut tar = TarWriter::new(writer);
+    let mut files = BTreeMap::new();
+    files.insert(
+        "config.json".to_owned(),
+        ManifestFile {
+            size: config_json.len() as u64,
+            sha256: tar.append_data("config.json", config_json.as_bytes())?,
+        },
+    );
+    // seed.iso exists if vm never completed first boot
+    let seed = dir.seed_path.exists().then_some(("seed.iso", &dir.seed_path));
+    for (name, path) in [("nvram.bin", &dir.nvram_path)].into_iter().chain(seed) {
+        let data = fs::read(path).or_io_error("failed to read file")?;
+        let sha256 = tar.append_data(name, &data)?;
+        files.insert(name.to_owned(), ManifestFile { size: data.len() as u64, sha256 });
+    }
+    let size = dir.disk_path.metadata().or_io_error("failed to read disk metadata")?.len();
+    let sha256 = tar.append_sparse_file("disk.img", &dir.disk_path)?;
+    files.insert("disk.img".to_owned(), ManifestFile { size, sha256 });
+
+    let manifest = Manifest { name: dir.name(), schema_version, files };
+    tar.append_data(MANIFEST_FILE, json::to_json_pretty(&manifest).as_bytes())?;
+    tar.finish()?;
+    Ok(())
+}
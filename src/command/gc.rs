@@ -0,0 +1,114 @@
+use std::fs;
+use std::os::unix::fs::MetadataExt as _;
+use std::path::Path;
+
+use clap::Args;
+use tracing::info;
+use tracing::warn;
+
+use crate::config::vm_dir;
+use crate::config::vm_dir::VmDir;
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::util::file_lock::DirLock;
+use crate::util::size;
+use crate::util::table::print_table;
+use crate::util::time;
+
+#[derive(Args)]
+pub struct Gc {
+    #[arg(long, help = "only remove temp dirs older than hours, dirs younger than 10 minutes are always kept", default_value_t = DEFAULT_AGE_HOURS)]
+    older_than: u64,
+
+    #[arg(long, help = "only report temp dirs, do not remove")]
+    dry_run: bool,
+}
+
+// temp dir of running create / import must not be removed, installing macOS or importing large archive takes time
+const DEFAULT_AGE_HOURS: u64 = 24;
+// in seconds, temp dir may not be locked yet right after created
+const MIN_AGE: u64 = 600;
+
+struct TempDir {
+    dir: VmDir,
+    // in seconds
+    age: u64,
+    size: u64,
+}
+
+impl Gc {
+    pub fn execute(&self) -> Result<()> {
+        let temp_dirs = temp_dirs(self.older_than);
+        if temp_dirs.is_empty() {
+            info!("no temp vm dir to remove");
+            return Ok(());
+        }
+
+        let rows: Vec<[String; 3]> = temp_dirs
+            .iter()
+            .map(|temp_dir| {
+                [
+                    temp_dir.dir.dir.to_string_lossy().to_string(),
+                    format_age(temp_dir.age),
+                    size::format_decimal(temp_dir.size),
+                ]
+            })
+            .collect();
+        print_table(["dir", "age", "size"], &rows);
+
+        if self.dry_run {
+            return Ok(());
+        }
+        for temp_dir in temp_dirs {
+            info!("remove temp vm dir, dir={}", temp_dir.dir.dir.to_string_lossy());
+            fs::remove_dir_all(&temp_dir.dir.dir).or_io_error("failed to remove temp vm dir")?;
+        }
+        Ok(())
+    }
+}
+
+// called by create, to remind leftover of failed create
+pub fn check() {
+    let temp_dirs = temp_dirs(DEFAULT_AGE_HOURS);
+    if !temp_dirs.is_empty() {
+        let size: u64 = temp_dirs.iter().map(|temp_dir| temp_dir.size).sum();
+        warn!(
+            "found orphaned temp vm dirs, count={}, size={}, run 'vz gc' to remove",
+            temp_dirs.len(),
+            size::format_decimal(size)
+        );
+    }
+}
+
+// uninitialized temp dirs, initialized one may be vm being moved, dirs locked by running create / import / clone are skipped
+fn temp_dirs(older_than_hours: u64) -> Vec<TempDir> {
+    let now = time::now();
+    let min_age = (older_than_hours * 3600).max(MIN_AGE);
+    vm_dir::temp_vm_dirs()
+        .into_iter()
+        .filter(|dir| !dir.initialized() && !DirLock::locked(&dir.dir))
+        .filter_map(|dir| {
+            let age = now.saturating_sub(vm_dir::temp_vm_dir_created_at(&dir)?);
+            (age >= min_age).then(|| TempDir { size: allocated_size(&dir.dir), dir, age })
+        })
+        .collect()
+}
+
+// allocated size, so sparse disk.img is not counted by file size
+fn allocated_size(path: &Path) -> u64 {
+    let Ok(read_dir) = path.read_dir() else {
+        return 0;
+    };
+    read_dir
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => allocated_size(&entry.path()),
+            Ok(metadata) => metadata.blocks() * 512,
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+fn format_age(seconds: u64) -> String {
+    if seconds >= 86400 { format!("{}d", seconds / 86400) } else { format!("{}h", seconds / 3600) }
+}
@@ -0,0 +1,150 @@
+use std::fs;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use clap::Args;
+use clap::ValueHint;
+use tracing::info;
+use tracing::warn;
+
+use crate::command::export::MANIFEST_FILE;
+use crate::command::export::Manifest;
+use crate::command::export::ManifestFile;
+use crate::config::cloud_init;
+use crate::config::migration;
+use crate::config::vm_dir;
+use crate::config::vm_dir::VmDir;
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::json;
+use crate::util::tar::Entry;
+use crate::util::tar::TarReader;
+use crate::util::zstd;
+
+#[derive(Args)]
+pub struct Import {
+    #[arg(help = "archive file created by vz export", value_hint = ValueHint::FilePath)]
+    path: PathBuf,
+
+    #[arg(help = "vm name [default: name in archive]")]
+    name: Option<String>,
+}
+
+impl Import {
+    pub fn execute(&self) -> Result<()> {
+        if !self.path.exists() {
+            return Err(VzError::InvalidArgument(format!("file does not exist, path={}", self.path.to_string_lossy())));
+        }
+        if let Some(name) = &self.name {
+            validate_target(name)?;
+        }
+        zstd::check()?;
+
+        let (temp_dir, _temp_lock) = vm_dir::create_temp_vm_dir()?;
+        let result = self.import(&temp_dir);
+        if result.is_err() {
+            info!("remove temp vm dir, dir={}", temp_dir.dir.to_string_lossy());
+            if let Err(err) = fs::remove_dir_all(&temp_dir.dir) {
+                warn!("failed to remove temp vm dir, dir={}, err={err}", temp_dir.dir.to_string_lossy());
+            }
+        }
+        result
+    }
+
+    fn import(&self, temp_dir: &VmDir) -> Result<()> {
+        info!("extract archive, path={}, dir={}", self.path.to_string_lossy(), temp_dir.dir.to_string_lossy());
+        let mut child = zstd::decompress(&self.path)?;
+        let entries = match child.stdout.take() {
+            Some(stdout) => TarReader::new(BufReader::new(stdout)).extract(&temp_dir.dir),
+            None => Err(VzError::InvalidArgument("failed to open zstd stdout".to_owned())),
+        };
+        // zstd exits once stdout is closed, if extraction stopped early
+        let status = zstd::wait(child);
+        let entries = entries?;
+        status?;
+
+        let manifest_path = temp_dir.dir.join(MANIFEST_FILE);
+        let json = fs::read_to_string(&manifest_path).or_io_error("failed to read manifest.json from archive")?;
+        let manifest: Manifest = json::from_json(&json)?;
+        verify(&manifest, &entries)?;
+        fs::remove_file(&manifest_path).or_io_error("failed to remove manifest.json")?;
+        if !temp_dir.initialized() {
+            return Err(VzError::InvalidArgument("invalid archive, vm files are missing".to_owned()));
+        }
+        // migrate and validate config
+        temp_dir.load_config()?;
+
+        let name = self.name.as_ref().unwrap_or(&manifest.name);
+        validate_target(name)?;
+        // new instance-id, so cloud-init treats imported vm as new instance
+        if temp_dir.seed_path.exists() {
+            cloud_init::copy_seed(&temp_dir.seed_path, &temp_dir.seed_path, name)?;
+        }
+        let vm_dir = vm_dir::vm_dir(name);
+        info!("move vm dir, from={}, to={}", temp_dir.dir.to_string_lossy(), vm_dir.dir.to_string_lossy());
+        fs::rename(&temp_dir.dir, &vm_dir.dir).or_io_error("failed to rename dir")?;
+        info!(name, "vm imported, config={}", vm_dir.config_path.to_string_lossy());
+        Ok(())
+    }
+}
+
+fn validate_target(name: &str) -> Result<()> {
+    vm_dir::validate_name(name)?;
+    if vm_dir::vm_dir(name).dir.exists() {
+        return Err(VzError::InvalidArgument(format!("vm already exists, name={name}")));
+    }
+    Ok(())
+}
+
+// every file must match manifest, and every file in manifest must exist
+fn verify(manifest: &Manifest, entries: &[Entry]) -> Result<()> {
+    if manifest.schema_version > migration::SCHEMA_VERSION {
+        return Err(VzError::InvalidConfig(format!(
+            "archive is created by newer version, schemaVersion={}, supported={}",
+            manifest.schema_version,
+            migration::SCHEMA_VERSION
+        )));
+    }
+    let mut verified = 0;
+    for entry in entries.iter().filter(|entry| entry.name != MANIFEST_FILE) {
+        let expected = manifest.files.get(&entry.name).ok_or_else(|| {
+            VzError::InvalidArgument(format!("invalid archive, file is not in manifest, file={}", entry.name))
+        })?;
+        let actual = ManifestFile { size: entry.size, sha256: entry.sha256.clone() };
+        if *expected != actual {
+            return Err(VzError::InvalidArgument(format!(
+                "invalid archive, checksum mismatch, file={}, expected={}, actual={}",
+                entry.name, expected.sha256, actual.sha256
+            )));
+        }
+        verified += 1;
+    }
+    if verified != manifest.files.len() {
+        return Err(VzError::InvalidArgument("invalid archive, files in manifest are missing".to_owned()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn entry(name: &str, sha256: &str) -> Entry {
+        Entry { name: name.to_owned(), size: 2, sha256: sha256.to_owned() }
+    }
+
+    #[test]
+    fn verify_entries() {
+        let mut files = BTreeMap::new();
+        files.insert("config.json".to_owned(), ManifestFile { size: 2, sha256: "a".to_owned() });
+        let manifest = Manifest { name: "debian".to_owned(), schema_version: migration::SCHEMA_VERSION, files };
+
+        assert!(verify(&manifest, &[entry("config.json", "a"), entry(MANIFEST_FILE, "m")]).is_ok(), "valid");
+        assert!(verify(&manifest, &[entry("config.json", "b")]).is_err(), "checksum mismatch");
+        assert!(verify(&manifest, &[entry("config.json", "a"), entry("disk.img", "a")]).is_err(), "unknown file");
+        assert!(verify(&manifest, &[]).is_err(), "missing file");
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+use std::os::unix::fs::MetadataExt as _;
+use std::path::PathBuf;
+
+use clap::Args;
+use libc::pid_t;
+use serde::Serialize;
+
+use crate::command::list::Status;
+use crate::config::global_config;
+use crate::config::vm_config::Os;
+use crate::config::vm_config::Ssh;
+use crate::config::vm_dir;
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::util::guest_ip;
+use crate::util::json;
+use crate::util::path::PathExtension as _;
+
+#[derive(Args)]
+pub struct Inspect {
+    #[arg(help = "vm name")]
+    name: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Inspection {
+    name: String,
+    status: Status,
+    pid: Option<pid_t>,
+    schema_version: u64,
+    os: Os,
+    cpu: usize,
+    ram: u64,
+    disk: Disk,
+    mac_address: String,
+    ip: Option<String>,
+    sharing: BTreeMap<String, PathBuf>,
+    rosetta: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hardware_model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    machine_identifier: Option<String>,
+    ssh: Ssh,
+    ports: Vec<String>,
+    paths: Paths,
+}
+
+#[derive(Serialize, Debug)]
+struct Disk {
+    allocated: u64,
+    size: u64,
+}
+
+#[derive(Serialize, Debug)]
+struct Paths {
+    dir: PathBuf,
+    config: PathBuf,
+    disk: PathBuf,
+    nvram: PathBuf,
+}
+
+impl Inspect {
+    pub fn execute(&self) -> Result<()> {
+        let dir = vm_dir::initialized_vm_dir(&self.name)?;
+        let config = dir.load_config()?;
+        let metadata = dir.disk_path.metadata().or_io_error("failed to get metadata")?;
+        let ip_addrs = guest_ip::ip_addrs(&global_config::get().dhcpd_leases())?;
+        let pid = dir.pid();
+
+        let inspection = Inspection {
+            name: dir.name(),
+            status: if pid.is_some() { Status::Running } else { Status::Stopped },
+            pid,
+            schema_version: config.schema_version,
+            os: config.os,
+            cpu: config.cpu,
+            ram: config.ram,
+            disk: Disk { allocated: metadata.blocks() * 512, size: metadata.len() },
+            ip: ip_addrs.get(&config.mac_address).cloned(),
+            mac_address: config.mac_address,
+            sharing: config
+                .sharing
+                .into_iter()
+                .map(|(name, path)| (name, PathBuf::from(path).to_absolute_path()))
+                .collect(),
+            rosetta: config.rosetta.unwrap_or(false),
+            hardware_model: config.hardware_model,
+            machine_identifier: config.machine_identifier,
+            ssh: config.ssh,
+            ports: config.ports,
+            paths: Paths {
+                dir: dir.dir.clone(),
+                config: dir.config_path.clone(),
+                disk: dir.disk_path.clone(),
+                nvram: dir.nvram_path.clone(),
+            },
+        };
+        println!("{}", json::to_json_pretty(&inspection));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn serialize_inspection() {
+        let inspection = Inspection {
+            name: "debian".to_owned(),
+            status: Status::Stopped,
+            pid: None,
+            schema_version: 1,
+            os: Os::Linux,
+            cpu: 1,
+            ram: 1024 * 1024 * 1024,
+            disk: Disk { allocated: 0, size: 0 },
+            mac_address: "f6:db:b3:ec:f9:3f".to_owned(),
+            ip: None,
+            sharing: BTreeMap::new(),
+            rosetta: false,
+            hardware_model: None,
+            machine_identifier: None,
+            ssh: Ssh { user: Some("me".to_owned()), port: Some(2222), identity_file: None },
+            ports: vec!["8080:80".to_owned()],
+            paths: Paths {
+                dir: PathBuf::from("/vz/debian"),
+                config: PathBuf::from("/vz/debian/config.json"),
+                disk: PathBuf::from("/vz/debian/disk.img"),
+                nvram: PathBuf::from("/vz/debian/nvram.bin"),
+            },
+        };
+        let value = json::to_value(&inspection);
+        assert_eq!(Some(&json!({"user": "me", "port": 2222})), value.get("ssh"));
+        assert_eq!(Some(&json!(["8080:80"])), value.get("ports"));
+        assert_eq!(None, value.get("hardwareModel"));
+    }
+}
@@ -5,8 +5,11 @@ use clap::ValueHint;
 use objc2_foundation::MainThreadMarker;
 use tracing::info;
 
+use crate::config::validation;
 use crate::config::vm_config::Os;
 use crate::config::vm_dir;
+use crate::error::Result;
+use crate::error::VzError;
 use crate::util::path::PathExtension as _;
 use crate::vm::mac_os;
 use crate::vm::mac_os_installer;
@@ -21,23 +24,28 @@ pub struct Install {
 }
 
 impl Install {
-    pub fn execute(&self) {
-        self.validate();
+    pub fn execute(&self) -> Result<()> {
+        self.validate()?;
 
-        let name = &self.name;
-        let dir = vm_dir::vm_dir(name);
-        assert!(dir.initialized(), "vm not initialized, name={name}");
-        let config = dir.load_config();
-        assert!(matches!(config.os, Os::MacOs), "install requires macOS guest");
-        let _lock = dir.lock();
+        let dir = vm_dir::initialized_vm_dir(&self.name)?;
+        let config = dir.load_config()?;
+        validation::check(&config)?;
+        if !matches!(config.os, Os::MacOs) {
+            return Err(VzError::InvalidArgument("install requires macOS guest".to_owned()));
+        }
+        let _lock = dir.lock()?;
 
         info!("install macOS");
         let marker = MainThreadMarker::new().unwrap();
-        let vm = mac_os::create_vm(&dir, &config, marker);
+        let vm = mac_os::create_vm(&dir, &config, marker)?;
         mac_os_installer::install(&vm, &self.ipsw.to_absolute_path(), marker);
+        Ok(())
     }
 
-    fn validate(&self) {
-        assert!(self.ipsw.exists(), "ipsw does not exist, path={}", self.ipsw.to_string_lossy());
+    fn validate(&self) -> Result<()> {
+        if !self.ipsw.exists() {
+            return Err(VzError::InvalidArgument(format!("ipsw does not exist, path={}", self.ipsw.to_string_lossy())));
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,58 @@
+use std::thread::sleep;
+use std::time::Duration;
+use std::time::Instant;
+
+use clap::Args;
+use tracing::info;
+
+use crate::config::global_config;
+use crate::config::hosts;
+use crate::config::vm_dir;
+use crate::config::vm_dir::VmDir;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::guest_ip;
+
+#[derive(Args)]
+pub struct Ip {
+    #[arg(help = "vm name")]
+    name: String,
+    #[arg(long, help = "seconds to wait until ip is assigned", default_value_t = 0)]
+    wait: u64,
+}
+
+const MIN_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+impl Ip {
+    pub fn execute(&self) -> Result<()> {
+        let dir = vm_dir::initialized_vm_dir(&self.name)?;
+        let ip = wait_ip(&dir, Duration::from_secs(self.wait))?;
+        println!("{ip}");
+        hosts::update_hosts(None);
+        Ok(())
+    }
+}
+
+// guest gets ip after boot, poll lease file and arp table until timeout
+pub fn wait_ip(dir: &VmDir, timeout: Duration) -> Result<String> {
+    let name = dir.name();
+    let mac_address = dir.load_config()?.mac_address;
+    let deadline = Instant::now() + timeout;
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        if dir.pid().is_none() {
+            return Err(VzError::VmNotRunning(name));
+        }
+        if let Some(ip) = guest_ip::ip_addrs(&global_config::get().dhcpd_leases())?.remove(&mac_address) {
+            return Ok(ip);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(VzError::Timeout(format!("vm has no ip, name={name}, mac_address={mac_address}")));
+        }
+        info!("wait for vm ip, name={name}, backoff={backoff:?}");
+        sleep(backoff.min(deadline - now));
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
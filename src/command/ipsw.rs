@@ -5,21 +5,30 @@ use clap::Args;
 use objc2_foundation::NSError;
 use objc2_virtualization::VZMacOSRestoreImage;
 
+use crate::error::Result;
+use crate::error::VzError;
+
 #[derive(Args)]
 pub struct Ipsw;
 
 impl Ipsw {
-    pub fn execute() {
+    pub fn execute() -> Result<()> {
         let (tx, rx) = channel();
         let block = StackBlock::new(move |image: *mut VZMacOSRestoreImage, err: *mut NSError| {
-            assert!(err.is_null(), "failed to fetch macos image, err={}", unsafe { (*err).localizedDescription() });
-            let url = unsafe { (*image).URL().absoluteString().unwrap() };
-            tx.send(url).unwrap();
+            let result = if err.is_null() {
+                Ok(unsafe { (*image).URL().absoluteString().unwrap() })
+            } else {
+                Err(VzError::Framework(format!("failed to fetch macos image, err={}", unsafe {
+                    (*err).localizedDescription()
+                })))
+            };
+            tx.send(result).unwrap();
         });
         unsafe {
             VZMacOSRestoreImage::fetchLatestSupportedWithCompletionHandler(&block);
         };
-        let url = rx.recv().unwrap();
+        let url = rx.recv().map_err(|err| VzError::Framework(format!("failed to fetch macos image, err={err}")))??;
         println!("{url}");
+        Ok(())
     }
 }
@@ -0,0 +1,52 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use clap::Args;
+use clap::Subcommand;
+use clap::ValueHint;
+use tracing::info;
+
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::iso9660;
+
+#[derive(Args)]
+pub struct Iso {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    #[command(about = "build iso9660 image with joliet names from dir")]
+    Build {
+        #[arg(help = "iso image, e.g. out.iso", value_hint = ValueHint::FilePath)]
+        path: PathBuf,
+        #[arg(help = "dir of files to include", value_hint = ValueHint::DirPath)]
+        dir: PathBuf,
+        #[arg(long, help = "volume label, e.g. cidata [default: dir name]")]
+        label: Option<String>,
+    },
+}
+
+impl Iso {
+    pub fn execute(&self) -> Result<()> {
+        match &self.action {
+            Action::Build { path, dir, label } => build(path, dir, label.as_deref()),
+        }
+    }
+}
+
+fn build(path: &Path, dir: &Path, label: Option<&str>) -> Result<()> {
+    if !dir.is_dir() {
+        return Err(VzError::InvalidArgument(format!("dir does not exist, path={}", dir.to_string_lossy())));
+    }
+    if path.exists() {
+        return Err(VzError::InvalidArgument(format!("iso already exists, path={}", path.to_string_lossy())));
+    }
+    let label = label.map_or_else(|| iso9660::volume_label(dir), str::to_owned);
+    let nodes = iso9660::scan_dir(dir)?;
+    iso9660::write(path, &label, &nodes)?;
+    info!("iso built, path={}, label={label}", path.to_string_lossy());
+    Ok(())
+}
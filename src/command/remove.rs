@@ -0,0 +1,25 @@
+use clap::Args;
+
+use crate::command::stop;
+use crate::config::vm_dir;
+use crate::error::Result;
+
+#[derive(Args)]
+pub struct Remove {
+    #[arg(help = "vm name")]
+    name: String,
+
+    #[arg(long, help = "stop vm first if it is running")]
+    force: bool,
+}
+
+impl Remove {
+    pub fn execute(&self) -> Result<()> {
+        let dir = vm_dir::initialized_vm_dir(&self.name)?;
+        if self.force && dir.pid().is_some() {
+            stop::stop(&dir)?;
+        }
+        dir.ensure_stopped()?;
+        dir.remove()
+    }
+}
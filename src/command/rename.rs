@@ -0,0 +1,38 @@
+use std::fs;
+
+use clap::Args;
+use tracing::info;
+
+use crate::config::hosts;
+use crate::config::vm_dir;
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+
+#[derive(Args)]
+pub struct Rename {
+    #[arg(help = "vm name")]
+    name: String,
+
+    #[arg(help = "new vm name")]
+    new_name: String,
+}
+
+impl Rename {
+    pub fn execute(&self) -> Result<()> {
+        let dir = vm_dir::initialized_vm_dir(&self.name)?;
+        dir.ensure_stopped()?;
+        vm_dir::validate_name(&self.new_name)?;
+        let new_dir = vm_dir::vm_dir(&self.new_name);
+        if new_dir.dir.exists() {
+            return Err(VzError::InvalidArgument(format!("vm already exists, name={}", self.new_name)));
+        }
+
+        // hold lock to prevent vm from starting during rename
+        let _lock = dir.lock()?;
+        info!("rename vm, from={}, to={}", self.name, self.new_name);
+        fs::rename(&dir.dir, &new_dir.dir).or_io_error("failed to rename dir")?;
+        hosts::update_hosts(None);
+        Ok(())
+    }
+}
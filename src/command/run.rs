@@ -1,5 +1,7 @@
 use std::env::current_exe;
+use std::fs;
 use std::fs::File;
+use std::path::Path;
 use std::path::PathBuf;
 use std::process;
 use std::process::Command;
@@ -39,13 +41,21 @@ use signal_hook::iterator::Signals;
 use tracing::info;
 use tracing::info_span;
 
+use crate::config::global_config;
+use crate::config::hosts;
+use crate::config::validation;
 use crate::config::vm_config::Os;
 use crate::config::vm_dir;
-use crate::util::path::PathExtension as _;
+use crate::config::vm_dir::VmDir;
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::iso9660;
 use crate::vm;
 use crate::vm::gui_delegate::GuiDelegate;
 use crate::vm::linux;
 use crate::vm::mac_os;
+use crate::vm::port_forward;
 use crate::vm::vm_delegate::VmDelegate;
 
 #[derive(Args)]
@@ -58,15 +68,16 @@ pub struct Run {
     detached: bool,
     #[arg(long, help = "attach disk image in read only mode, e.g. --mount=debian.iso", value_hint = ValueHint::FilePath)]
     mount: Option<PathBuf>,
+    #[arg(long, help = "attach dir as read only iso image, volume label is dir name", value_hint = ValueHint::DirPath)]
+    attach_dir: Option<PathBuf>,
 }
 
 impl Run {
-    pub fn execute(&self) {
-        self.validate();
+    pub fn execute(&self) -> Result<()> {
+        self.validate()?;
 
         let name = &self.name;
-        let dir = vm_dir::vm_dir(name);
-        assert!(dir.initialized(), "vm not initialized, name={name}");
+        let dir = vm_dir::initialized_vm_dir(name)?;
 
         if self.detached {
             return run_in_background(name);
@@ -75,28 +86,42 @@ impl Run {
         let span = info_span!("run_vm", name = self.name, pid = process::id());
         let enter = span.enter();
 
-        let config = dir.load_config();
+        let config = dir.load_config()?;
+        validation::check(&config)?;
 
         // must after vm_dir.load_config(), it cloese config file and release all fd
         // must hold lock reference, otherwise fd will be deallocated, and release all locks
-        let _lock = dir.lock();
+        let _lock = dir.lock()?;
+
+        let mut mounts: Vec<PathBuf> = self.mount.iter().cloned().collect();
+        if let Some(attach_dir) = &self.attach_dir {
+            attach(&dir, attach_dir)?;
+            mounts.push(dir.attach_path.clone());
+        } else if dir.attach_path.exists() {
+            fs::remove_file(&dir.attach_path).or_io_error("failed to remove attach.iso")?;
+        }
+
+        // bind before vm starts, so port conflict fails fast
+        port_forward::start(&config)?;
+        hosts::update_hosts(Some((name, &config.mac_address)));
 
         let marker = MainThreadMarker::new().unwrap();
         let vm = match config.os {
-            Os::Linux => linux::create_vm(&dir, &config, self.gui, self.mount.as_ref()),
-            Os::MacOs => mac_os::create_vm(&dir, &config, marker),
+            Os::Linux => linux::create_vm(&dir, &config, self.gui, &mounts)?,
+            Os::MacOs => mac_os::create_vm(&dir, &config, marker)?,
         };
         let proto: Retained<ProtocolObject<dyn VZVirtualMachineDelegate>> =
-            ProtocolObject::from_retained(VmDelegate::new());
+            ProtocolObject::from_retained(VmDelegate::new(dir.seed_path.exists().then(|| dir.seed_path.clone())));
         unsafe {
             vm.setDelegate(Some(&proto));
         }
         let vm = Arc::new(MainThreadBound::new(vm, marker));
         vm::start_vm(&vm);
+        hosts::update_hosts_when_assigned(name.to_owned(), config.mac_address.clone());
 
         drop(enter);
 
-        handle_signal(name.to_owned(), Arc::clone(&vm));
+        handle_signal(name.to_owned(), Arc::clone(&vm))?;
 
         if self.gui {
             let auto_reconfig_display = matches!(&config.os, Os::MacOs);
@@ -104,48 +129,68 @@ impl Run {
         } else {
             dispatch_main();
         }
+        Ok(())
     }
 
-    fn validate(&self) {
-        if let Some(path) = &self.mount {
-            assert!(path.exists(), "mount does not exist, path={}", path.to_string_lossy());
+    fn validate(&self) -> Result<()> {
+        if let Some(path) = &self.mount
+            && !path.exists()
+        {
+            return Err(VzError::InvalidArgument(format!("mount does not exist, path={}", path.to_string_lossy())));
+        }
+
+        if let Some(path) = &self.attach_dir
+            && !path.is_dir()
+        {
+            return Err(VzError::InvalidArgument(format!(
+                "attach dir does not exist, path={}",
+                path.to_string_lossy()
+            )));
         }
 
-        assert!(!(self.detached && (self.gui || self.mount.is_some())), "-d must not be used with --gui and --mount");
+        if self.detached && (self.gui || self.mount.is_some() || self.attach_dir.is_some()) {
+            return Err(VzError::InvalidArgument(
+                "-d must not be used with --gui, --mount and --attach-dir".to_owned(),
+            ));
+        }
+        Ok(())
     }
 }
 
+fn attach(dir: &VmDir, attach_dir: &Path) -> Result<()> {
+    info!("build attach.iso, dir={}", attach_dir.to_string_lossy());
+    let nodes = iso9660::scan_dir(attach_dir)?;
+    iso9660::write(&dir.attach_path, &iso9660::volume_label(attach_dir), &nodes)
+}
+
 #[allow(clippy::zombie_processes)]
-fn run_in_background(name: &str) {
-    let log_path = PathBuf::from("~/Library/Logs/vz.log").to_absolute_path();
+fn run_in_background(name: &str) -> Result<()> {
+    let log_dir = global_config::get().log_dir();
+    fs::create_dir_all(&log_dir).or_io_error("failed to create log dir")?;
+    let log_path = log_dir.join("vz.log");
 
     if let Ok(metadata) = log_path.metadata()
         && (!metadata.is_file() || metadata.permissions().readonly())
     {
-        panic!("log file is not writable, path={}", log_path.to_string_lossy());
+        return Err(VzError::InvalidArgument(format!("log file is not writable, path={}", log_path.to_string_lossy())));
     }
 
-    let mut command =
-        Command::new(current_exe().unwrap_or_else(|err| panic!("failed to get current command path, err={err}")));
+    let mut command = Command::new(current_exe().or_io_error("failed to get current command path")?);
     command.args(["run", name]);
-    command.stdout(log_file_io(&log_path));
-    command.stderr(log_file_io(&log_path));
-    command.spawn().unwrap_or_else(|err| panic!("failed to run command, err={err}"));
+    command.stdout(log_file_io(&log_path)?);
+    command.stderr(log_file_io(&log_path)?);
+    command.spawn().or_io_error("failed to run command")?;
     info!("vm launched in background, check log in {}", log_path.to_string_lossy());
+    Ok(())
 }
 
-fn log_file_io(log_path: &PathBuf) -> Stdio {
-    Stdio::from(
-        File::options()
-            .create(true)
-            .append(true)
-            .open(log_path)
-            .unwrap_or_else(|err| panic!("failed to open log file, err={err}")),
-    )
+fn log_file_io(log_path: &PathBuf) -> Result<Stdio> {
+    let file = File::options().create(true).append(true).open(log_path).or_io_error("failed to open log file")?;
+    Ok(Stdio::from(file))
 }
 
-fn handle_signal(name: String, vm: Arc<MainThreadBound<Retained<VZVirtualMachine>>>) {
-    let mut signals = Signals::new([SIGTERM, SIGINT, SIGQUIT]).unwrap();
+fn handle_signal(name: String, vm: Arc<MainThreadBound<Retained<VZVirtualMachine>>>) -> Result<()> {
+    let mut signals = Signals::new([SIGTERM, SIGINT, SIGQUIT]).or_io_error("failed to register signal handler")?;
     thread::spawn(move || {
         let signal = signals.forever().next().unwrap();
         info!(name, pid = process::id(), signal, "received signal");
@@ -158,6 +203,7 @@ fn handle_signal(name: String, vm: Arc<MainThreadBound<Retained<VZVirtualMachine
             }
         }
     });
+    Ok(())
 }
 
 fn run_gui(
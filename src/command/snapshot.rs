@@ -0,0 +1,193 @@
+use std::fs;
+use std::os::unix::fs::MetadataExt as _;
+use std::path::PathBuf;
+
+use clap::Args;
+use clap::Subcommand;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::info;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::config::vm_dir;
+use crate::config::vm_dir::VmDir;
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::file_lock::FileLock;
+use crate::util::json;
+use crate::util::size;
+use crate::util::sparse_file;
+use crate::util::table::print_table;
+use crate::util::time;
+
+#[derive(Args)]
+pub struct Snapshot {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    #[command(about = "snapshot disk.img, nvram.bin and config.json of stopped vm")]
+    Create {
+        #[arg(help = "vm name")]
+        name: String,
+        #[arg(help = "snapshot tag")]
+        tag: String,
+    },
+    #[command(about = "list snapshots")]
+    Ls {
+        #[arg(help = "vm name")]
+        name: String,
+    },
+    #[command(about = "restore vm to snapshot, current disk.img, nvram.bin and config.json will be overwritten")]
+    Restore {
+        #[arg(help = "vm name")]
+        name: String,
+        #[arg(help = "snapshot tag")]
+        tag: String,
+    },
+    #[command(about = "remove snapshot")]
+    Rm {
+        #[arg(help = "vm name")]
+        name: String,
+        #[arg(help = "snapshot tag")]
+        tag: String,
+    },
+}
+
+// snapshots/[tag]/snapshot.json
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SnapshotInfo {
+    tag: String,
+    // seconds since epoch
+    created_at: u64,
+}
+
+impl Snapshot {
+    pub fn execute(&self) -> Result<()> {
+        match &self.action {
+            Action::Create { name, tag } => create(name, tag),
+            Action::Ls { name } => list(name),
+            Action::Restore { name, tag } => restore(name, tag),
+            Action::Rm { name, tag } => remove(name, tag),
+        }
+    }
+}
+
+fn create(name: &str, tag: &str) -> Result<()> {
+    let dir = vm_dir::initialized_vm_dir(name)?;
+    vm_dir::validate_name(tag)?;
+    dir.ensure_stopped()?;
+    // hold lock to prevent vm from starting during copy
+    let lock = dir.lock()?;
+
+    let snapshot = dir.snapshot_dir(tag);
+    if snapshot.dir.exists() {
+        return Err(VzError::InvalidArgument(format!("snapshot already exists, name={name}, tag={tag}")));
+    }
+
+    let temp_dir = dir.snapshot_dir(&Uuid::now_v7().to_string());
+    fs::create_dir_all(&temp_dir.dir).or_io_error("failed to create snapshot dir")?;
+    let result = copy_snapshot(&dir, &lock, &temp_dir, tag);
+    if result.is_err() {
+        info!("remove temp snapshot dir, dir={}", temp_dir.dir.to_string_lossy());
+        if let Err(err) = fs::remove_dir_all(&temp_dir.dir) {
+            warn!("failed to remove temp snapshot dir, dir={}, err={err}", temp_dir.dir.to_string_lossy());
+        }
+    }
+    result?;
+
+    fs::rename(&temp_dir.dir, &snapshot.dir).or_io_error("failed to rename dir")?;
+    info!("snapshot created, name={name}, tag={tag}");
+    Ok(())
+}
+
+fn copy_snapshot(dir: &VmDir, lock: &FileLock, temp_dir: &VmDir, tag: &str) -> Result<()> {
+    info!("copy disk, from={}, to={}", dir.disk_path.to_string_lossy(), temp_dir.disk_path.to_string_lossy());
+    sparse_file::copy(&dir.disk_path, &temp_dir.disk_path)?;
+    fs::copy(&dir.nvram_path, &temp_dir.nvram_path).or_io_error("failed to copy nvram.bin")?;
+    // config.json is lock file, read it via lock, otherwise lock is released
+    let config_json = lock.read().or_io_error("failed to load config")?;
+    fs::write(&temp_dir.config_path, config_json).or_io_error("failed to copy config.json")?;
+    let info = SnapshotInfo { tag: tag.to_owned(), created_at: time::now() };
+    fs::write(info_path(temp_dir), json::to_json_pretty(&info)).or_io_error("failed to save snapshot.json")
+}
+
+fn list(name: &str) -> Result<()> {
+    let dir = vm_dir::initialized_vm_dir(name)?;
+    let mut snapshots = vec![];
+    for snapshot in dir.snapshot_dirs() {
+        let json = fs::read_to_string(info_path(&snapshot)).or_io_error("failed to load snapshot.json")?;
+        let info: SnapshotInfo = json::from_json(&json)?;
+        let metadata = snapshot.disk_path.metadata().or_io_error("failed to read disk metadata")?;
+        snapshots.push((info, metadata));
+    }
+    snapshots.sort_by_key(|(info, _)| info.created_at);
+
+    let rows: Vec<[String; 3]> = snapshots
+        .into_iter()
+        .map(|(info, metadata)| {
+            [
+                info.tag,
+                time::format_utc(info.created_at),
+                format!("{}/{}", size::format_decimal(metadata.blocks() * 512), size::format_decimal(metadata.len())),
+            ]
+        })
+        .collect();
+    print_table(["tag", "created", "disk"], &rows);
+    Ok(())
+}
+
+fn restore(name: &str, tag: &str) -> Result<()> {
+    let dir = vm_dir::initialized_vm_dir(name)?;
+    dir.ensure_stopped()?;
+    let _lock = dir.lock()?;
+    let snapshot = initialized_snapshot_dir(&dir, tag)?;
+
+    // copy to temp files then rename, so vm files stay intact if copy fails
+    info!("restore disk, from={}, to={}", snapshot.disk_path.to_string_lossy(), dir.disk_path.to_string_lossy());
+    let temp_disk_path = dir.dir.join("disk.img.restore");
+    if temp_disk_path.exists() {
+        fs::remove_file(&temp_disk_path).or_io_error("failed to remove file")?;
+    }
+    sparse_file::copy(&snapshot.disk_path, &temp_disk_path)?;
+    let temp_nvram_path = dir.dir.join("nvram.bin.restore");
+    fs::copy(&snapshot.nvram_path, &temp_nvram_path).or_io_error("failed to copy nvram.bin")?;
+    let temp_config_path = dir.dir.join("config.json.restore");
+    fs::copy(&snapshot.config_path, &temp_config_path).or_io_error("failed to copy config.json")?;
+    fs::rename(&temp_disk_path, &dir.disk_path).or_io_error("failed to rename disk.img")?;
+    fs::rename(&temp_nvram_path, &dir.nvram_path).or_io_error("failed to rename nvram.bin")?;
+    // config.json is lock file, replace it last, lock is held on replaced file until restore returns
+    fs::rename(&temp_config_path, &dir.config_path).or_io_error("failed to rename config.json")?;
+    info!("snapshot restored, name={name}, tag={tag}");
+    Ok(())
+}
+
+fn remove(name: &str, tag: &str) -> Result<()> {
+    let dir = vm_dir::initialized_vm_dir(name)?;
+    dir.ensure_stopped()?;
+    let _lock = dir.lock()?;
+    let snapshot = initialized_snapshot_dir(&dir, tag)?;
+    snapshot.remove()?;
+    info!("snapshot removed, name={name}, tag={tag}");
+    Ok(())
+}
+
+// tag is used as dir name, validate it so it can't escape snapshots dir, e.g. ../../other
+fn initialized_snapshot_dir(dir: &VmDir, tag: &str) -> Result<VmDir> {
+    vm_dir::validate_name(tag)?;
+    let snapshot = dir.snapshot_dir(tag);
+    if snapshot.initialized() && info_path(&snapshot).exists() {
+        Ok(snapshot)
+    } else {
+        Err(VzError::InvalidArgument(format!("snapshot not found, name={}, tag={tag}", dir.name())))
+    }
+}
+
+fn info_path(snapshot: &VmDir) -> PathBuf {
+    snapshot.dir.join("snapshot.json")
+}
@@ -0,0 +1,103 @@
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::net::TcpStream;
+use std::os::unix::process::CommandExt as _;
+use std::path::PathBuf;
+use std::process::Command;
+use std::thread::sleep;
+use std::time::Duration;
+use std::time::Instant;
+
+use clap::Args;
+use tracing::info;
+
+use crate::command::ip;
+use crate::config::validation;
+use crate::config::vm_config;
+use crate::config::vm_dir;
+use crate::config::vm_dir::VmDir;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::path::PathExtension as _;
+
+#[derive(Args)]
+pub struct Ssh {
+    #[arg(help = "vm name")]
+    name: String,
+    #[arg(long, help = "seconds to wait until ssh port is open", default_value_t = 60)]
+    wait: u64,
+    #[arg(help = "command to run in vm, e.g. vz ssh debian -- uname -a", last = true)]
+    command: Vec<String>,
+}
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+impl Ssh {
+    pub fn execute(&self) -> Result<()> {
+        let dir = vm_dir::initialized_vm_dir(&self.name)?;
+        let config = dir.load_config()?;
+        validation::check_ssh(&config)?;
+        let deadline = Instant::now() + Duration::from_secs(self.wait);
+        let ip = ip::wait_ip(&dir, Duration::from_secs(self.wait))?;
+        wait_port(&ip, config.ssh.port(), deadline)?;
+
+        let mut command = Command::new("ssh");
+        command.args(["-o", &format!("HostName={ip}")]);
+        for (key, value) in options(&dir, &config.ssh) {
+            command.args(["-o", &format!("{key}={value}")]);
+        }
+        command.arg(&self.name).args(&self.command);
+        // replace current process, so ssh takes over terminal and exit code
+        let err = command.exec();
+        Err(VzError::Io("failed to execute ssh".to_owned(), err))
+    }
+}
+
+// ssh options except HostName, host key is pinned per vm, ip is reused after vm is removed
+pub fn options(dir: &VmDir, ssh: &vm_config::Ssh) -> Vec<(&'static str, String)> {
+    let mut options = vec![];
+    if let Some(user) = &ssh.user {
+        options.push(("User", user.clone()));
+    }
+    options.push(("Port", ssh.port().to_string()));
+    if let Some(identity_file) = &ssh.identity_file {
+        options.push(("IdentityFile", quote(&PathBuf::from(identity_file).to_absolute_path().to_string_lossy())));
+        options.push(("IdentitiesOnly", "yes".to_owned()));
+    }
+    options.push(("HostKeyAlias", dir.name()));
+    options.push(("UserKnownHostsFile", quote(&dir.known_hosts_path.to_string_lossy())));
+    options.push(("StrictHostKeyChecking", "accept-new".to_owned()));
+    options
+}
+
+// ssh splits option value by whitespace
+fn quote(value: &str) -> String {
+    if value.contains(char::is_whitespace) { format!("\"{value}\"") } else { value.to_owned() }
+}
+
+// sshd starts after network is up, ip is known before port is open
+fn wait_port(ip: &str, port: u16, deadline: Instant) -> Result<()> {
+    let ip: IpAddr = ip.parse().map_err(|err| VzError::InvalidArgument(format!("invalid ip, ip={ip}, err={err}")))?;
+    let address = SocketAddr::new(ip, port);
+    loop {
+        if TcpStream::connect_timeout(&address, CONNECT_TIMEOUT).is_ok() {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(VzError::Timeout(format!("ssh port is not open, address={address}")));
+        }
+        info!("wait for ssh port, address={address}");
+        sleep(Duration::from_secs(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_value() {
+        assert_eq!("/Users/me/.ssh/id_ed25519", quote("/Users/me/.ssh/id_ed25519"));
+        assert_eq!("\"/Users/me/VM Home/debian/known_hosts\"", quote("/Users/me/VM Home/debian/known_hosts"));
+    }
+}
@@ -0,0 +1,54 @@
+use clap::Args;
+
+use crate::command::ssh;
+use crate::config::global_config;
+use crate::config::vm_dir;
+use crate::error::Result;
+use crate::util::guest_ip;
+
+#[derive(Args)]
+pub struct SshConfig;
+
+impl SshConfig {
+    pub fn execute() -> Result<()> {
+        let ip_addrs = guest_ip::ip_addrs(&global_config::get().dhcpd_leases())?;
+        let mut blocks = vec![];
+        for dir in vm_dir::vm_dirs() {
+            let config = dir.load_config()?;
+            let ip = ip_addrs.get(&config.mac_address).map(String::as_str);
+            blocks.push(host_block(&dir.name(), ip, &ssh::options(&dir, &config.ssh)));
+        }
+        print!("{}", blocks.join("\n"));
+        Ok(())
+    }
+}
+
+// vm without ip (e.g. stopped) has no HostName, run again after vm starts
+fn host_block(name: &str, ip: Option<&str>, options: &[(&str, String)]) -> String {
+    let mut lines = vec![format!("Host {name}")];
+    lines.push(match ip {
+        Some(ip) => format!("  HostName {ip}"),
+        None => format!("  # vm has no ip, start with 'vz run -d {name}'"),
+    });
+    lines.extend(options.iter().map(|(key, value)| format!("  {key} {value}")));
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_block_with_ip() {
+        let options = [("User", "me".to_owned()), ("Port", "22".to_owned())];
+        assert_eq!(
+            "Host debian\n  HostName 192.168.64.3\n  User me\n  Port 22\n",
+            host_block("debian", Some("192.168.64.3"), &options)
+        );
+        assert_eq!(
+            "Host debian\n  # vm has no ip, start with 'vz run -d debian'\n  User me\n  Port 22\n",
+            host_block("debian", None, &options)
+        );
+    }
+}
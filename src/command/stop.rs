@@ -1,13 +1,16 @@
-use std::process;
 use std::thread::sleep;
 use std::time::Duration;
+use std::time::Instant;
 
 use clap::Args;
-use tracing::error;
 use tracing::info;
 
+use crate::config::global_config;
+use crate::config::hosts;
 use crate::config::vm_dir;
 use crate::config::vm_dir::VmDir;
+use crate::error::Result;
+use crate::error::VzError;
 
 #[derive(Args)]
 pub struct Stop {
@@ -16,36 +19,38 @@ pub struct Stop {
 }
 
 impl Stop {
-    pub fn execute(&self) {
-        let name = &self.name;
-        let dir = vm_dir::vm_dir(name);
-        assert!(dir.initialized(), "vm not initialized, name={name}");
+    pub fn execute(&self) -> Result<()> {
+        let dir = vm_dir::initialized_vm_dir(&self.name)?;
+        stop(&dir)
+    }
+}
 
-        let pid = dir.pid().unwrap_or_else(|| panic!("vm not running, name={name}"));
-        info!("stop vm, name={name}, pid={pid}");
-        unsafe {
-            libc::kill(pid, libc::SIGINT);
-        }
+pub fn stop(dir: &VmDir) -> Result<()> {
+    let name = dir.name();
+    let pid = dir.pid().ok_or_else(|| VzError::VmNotRunning(name.clone()))?;
+    info!("stop vm, name={name}, pid={pid}");
+    unsafe {
+        libc::kill(pid, libc::SIGINT);
+    }
 
-        let success = wait_until_stopped(&dir);
-        if success {
-            info!("vm stopped");
-            process::exit(0);
-        } else {
-            error!("failed to stop vm");
-            process::exit(1);
-        }
+    let success = wait_until_stopped(dir);
+    if success {
+        info!("vm stopped");
+        hosts::update_hosts(None);
+        Ok(())
+    } else {
+        Err(VzError::Timeout(format!("failed to stop vm, name={name}, pid={pid}")))
     }
 }
 
+// vm process force stops guest after stop timeout, wait a bit longer than that
 fn wait_until_stopped(dir: &VmDir) -> bool {
-    let mut attempts = 0;
-    while attempts < 20 {
+    let deadline = Instant::now() + global_config::get().stop_timeout() + Duration::from_secs(5);
+    while Instant::now() < deadline {
         sleep(Duration::from_secs(1));
         if dir.pid().is_none() {
             return true;
         }
-        attempts += 1;
     }
     false
 }
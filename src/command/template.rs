@@ -0,0 +1,57 @@
+use clap::Args;
+use clap::Subcommand;
+
+use crate::config::template;
+use crate::config::vm_config::Os;
+use crate::error::Result;
+use crate::util::json;
+use crate::util::size;
+use crate::util::table::print_table;
+
+#[derive(Args)]
+pub struct Template {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    #[command(about = "list templates in ~/.config/vz/templates")]
+    Ls,
+    #[command(about = "show template")]
+    Show {
+        #[arg(help = "template name")]
+        name: String,
+    },
+}
+
+impl Template {
+    pub fn execute(&self) -> Result<()> {
+        match &self.action {
+            Action::Ls => {
+                let mut rows = vec![];
+                for name in template::names() {
+                    let template = template::load(&name)?;
+                    let os = match template.os {
+                        Some(Os::Linux) => "linux",
+                        Some(Os::MacOs) => "macOS",
+                        None => "-",
+                    };
+                    rows.push([
+                        name,
+                        os.to_owned(),
+                        template.cpu.map_or_else(|| "-".to_owned(), |cpu| cpu.to_string()),
+                        template.ram.map_or_else(|| "-".to_owned(), size::format_binary),
+                        template.disk.map_or_else(|| "-".to_owned(), size::format_decimal),
+                    ]);
+                }
+                print_table(["name", "os", "cpu", "ram", "disk"], &rows);
+            }
+            Action::Show { name } => {
+                let template = template::load(name)?;
+                println!("{}", json::to_json_pretty(&template));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use clap::Args;
+use tracing::info;
+
+use crate::config::validation;
+use crate::config::vm_dir;
+use crate::error::Result;
+use crate::error::VzError;
+
+#[derive(Args)]
+pub struct Validate {
+    #[arg(help = "vm name")]
+    name: String,
+}
+
+impl Validate {
+    pub fn execute(&self) -> Result<()> {
+        let dir = vm_dir::initialized_vm_dir(&self.name)?;
+        let config = dir.load_config()?;
+        // ssh problems only fail vz ssh
+        for warning in validation::validate_ssh(&config) {
+            println!("warning: {warning}");
+        }
+        let problems = validation::validate(&config);
+        if problems.is_empty() {
+            info!("config is valid, name={}", self.name);
+            return Ok(());
+        }
+        for problem in &problems {
+            println!("{problem}");
+        }
+        Err(VzError::InvalidConfig(format!("found {} problem(s), name={}", problems.len(), self.name)))
+    }
+}
@@ -1,2 +1,8 @@
+pub mod cloud_init;
+pub mod global_config;
+pub mod hosts;
+pub mod migration;
+pub mod template;
+pub mod validation;
 pub mod vm_config;
 pub mod vm_dir;
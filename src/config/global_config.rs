@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::util::json;
+use crate::util::path::PathExtension as _;
+
+static GLOBAL_CONFIG: OnceLock<GlobalConfig> = OnceLock::new();
+
+// ~/.config/vz/config.json, all fields are optional
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct GlobalConfig {
+    pub log_dir: Option<String>,
+    pub cpu: Option<usize>,
+    // in bytes
+    pub ram: Option<u64>,
+    // in bytes
+    pub disk: Option<u64>,
+    // in seconds
+    pub stop_timeout: Option<u64>,
+    pub dhcpd_leases: Option<String>,
+    pub hosts_file: Option<String>,
+}
+
+impl GlobalConfig {
+    pub fn log_dir(&self) -> PathBuf {
+        PathBuf::from(self.log_dir.as_deref().unwrap_or("~/Library/Logs")).to_absolute_path()
+    }
+
+    pub fn cpu(&self) -> usize {
+        self.cpu.unwrap_or(1)
+    }
+
+    pub fn ram(&self) -> u64 {
+        self.ram.unwrap_or(1024 * 1024 * 1024)
+    }
+
+    pub fn disk(&self) -> u64 {
+        self.disk.unwrap_or(50 * 1_000_000_000)
+    }
+
+    // time to wait for guest to shutdown before force stop
+    pub fn stop_timeout(&self) -> Duration {
+        Duration::from_secs(self.stop_timeout.unwrap_or(15))
+    }
+
+    // bootpd of macOS NAT network writes leases of vm
+    pub fn dhcpd_leases(&self) -> PathBuf {
+        PathBuf::from(self.dhcpd_leases.as_deref().unwrap_or("/var/db/dhcpd_leases")).to_absolute_path()
+    }
+
+    // <name>.vz entries of running vms are written to this file, opt-in, as /etc/hosts is only writable by root
+    pub fn hosts_file(&self) -> Option<PathBuf> {
+        self.hosts_file.as_deref().map(|path| PathBuf::from(path).to_absolute_path())
+    }
+}
+
+pub fn config_dir() -> PathBuf {
+    PathBuf::from("~/.config/vz").to_absolute_path()
+}
+
+// must be called once at startup, before any get()
+pub fn load() -> Result<()> {
+    let path = config_dir().join("config.json");
+    let config = if path.exists() {
+        let json = fs::read_to_string(&path).or_io_error("failed to load global config")?;
+        json::from_json(&json)?
+    } else {
+        GlobalConfig::default()
+    };
+    GLOBAL_CONFIG.get_or_init(|| config);
+    Ok(())
+}
+
+pub fn get() -> &'static GlobalConfig {
+    GLOBAL_CONFIG.get_or_init(GlobalConfig::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let config: GlobalConfig =
+            json::from_json(r#"{"logDir": "/var/log/vz", "cpu": 4, "stopTimeout": 30, "dhcpdLeases": "/tmp/leases"}"#)
+                .unwrap();
+        assert_eq!(PathBuf::from("/var/log/vz"), config.log_dir());
+        assert_eq!(4, config.cpu());
+        assert_eq!(1024 * 1024 * 1024, config.ram());
+        assert_eq!(Duration::from_secs(30), config.stop_timeout());
+        assert_eq!(PathBuf::from("/tmp/leases"), config.dhcpd_leases());
+        assert_eq!(None, config.hosts_file(), "hosts file is opt-in");
+    }
+}
@@ -0,0 +1,66 @@
+use std::thread;
+use std::thread::sleep;
+use std::time::Duration;
+use std::time::Instant;
+
+use tracing::info;
+use tracing::warn;
+
+use crate::config::global_config;
+use crate::config::vm_dir;
+use crate::error::Result;
+use crate::util::guest_ip;
+use crate::util::hosts;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+// linux guest gets ip in seconds, macOS guest may take longer to boot
+const HOSTS_WAIT: Duration = Duration::from_mins(5);
+
+// regenerate <name>.vz entries of running vms in hosts file if configured, failure is only logged
+// current is (name, mac_address) of vm running in this process, its config must not be opened,
+// as closing any fd of config.json releases the lock held by this process
+pub fn update_hosts(current: Option<(&str, &str)>) {
+    let Some(path) = global_config::get().hosts_file() else {
+        return;
+    };
+    let result = hosts_entries(current).and_then(|entries| hosts::update(&path, &entries));
+    match result {
+        Ok(true) => info!("hosts file updated, path={}", path.to_string_lossy()),
+        Ok(false) => {}
+        Err(err) => warn!("failed to update hosts file, path={}, err={err}", path.to_string_lossy()),
+    }
+}
+
+// called by vz run, update hosts file once guest gets ip
+pub fn update_hosts_when_assigned(name: String, mac_address: String) {
+    thread::spawn(move || {
+        let deadline = Instant::now() + HOSTS_WAIT;
+        while Instant::now() < deadline {
+            if guest_ip::ip_addrs(&global_config::get().dhcpd_leases())
+                .is_ok_and(|ip_addrs| ip_addrs.contains_key(&mac_address))
+            {
+                update_hosts(Some((&name, &mac_address)));
+                return;
+            }
+            sleep(POLL_INTERVAL);
+        }
+        warn!("vm has no ip, hosts file is not updated, name={name}");
+    });
+}
+
+fn hosts_entries(current: Option<(&str, &str)>) -> Result<Vec<(String, String)>> {
+    let ip_addrs = guest_ip::ip_addrs(&global_config::get().dhcpd_leases())?;
+    let mut entries = vec![];
+    for dir in vm_dir::vm_dirs() {
+        let name = dir.name();
+        let mac_address = match current {
+            Some((current_name, mac_address)) if current_name == name => mac_address.to_owned(),
+            _ if dir.pid().is_some() => dir.load_config()?.mac_address,
+            _ => continue,
+        };
+        if let Some(ip) = ip_addrs.get(&mac_address) {
+            entries.push((ip.clone(), format!("{name}.vz")));
+        }
+    }
+    Ok(entries)
+}
@@ -0,0 +1,164 @@
+use serde_json::Map;
+use serde_json::Value;
+
+use crate::error::Result;
+use crate::error::VzError;
+
+// every change to config.json layout must bump SCHEMA_VERSION and append a migration to MIGRATIONS
+pub const SCHEMA_VERSION: u64 = 3;
+
+type Migration = fn(&mut Map<String, Value>);
+
+// MIGRATIONS[n] upgrades config from version n to n + 1
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [v0_to_v1, v1_to_v2, v2_to_v3];
+
+// config.json without schemaVersion is treated as version 0
+pub fn schema_version(config: &Value) -> u64 {
+    config.get("schemaVersion").and_then(Value::as_u64).unwrap_or(0)
+}
+
+// return true if config is migrated
+pub fn migrate(config: &mut Value) -> Result<bool> {
+    let version = schema_version(config);
+    if version > SCHEMA_VERSION {
+        return Err(VzError::InvalidConfig(format!(
+            "config schema version is newer than supported, version={version}, supported={SCHEMA_VERSION}"
+        )));
+    }
+    if version == SCHEMA_VERSION {
+        return Ok(false);
+    }
+    let object =
+        config.as_object_mut().ok_or_else(|| VzError::InvalidConfig("config must be json object".to_owned()))?;
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        migration(object);
+    }
+    object.insert("schemaVersion".to_owned(), Value::from(SCHEMA_VERSION));
+    Ok(true)
+}
+
+// add schemaVersion, rename "memory" to "ram"
+fn v0_to_v1(config: &mut Map<String, Value>) {
+    if let Some(memory) = config.remove("memory") {
+        config.entry("ram").or_insert(memory);
+    }
+}
+
+// add empty ssh section, so "vz config [name] set ssh.user=me" works
+fn v1_to_v2(config: &mut Map<String, Value>) {
+    config.entry("ssh").or_insert_with(|| Value::Object(Map::new()));
+}
+
+// add empty port forwarding list
+fn v2_to_v3(config: &mut Map<String, Value>) {
+    config.entry("ports").or_insert_with(|| Value::Array(vec![]));
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::config::vm_config::VmConfig;
+    use crate::util::json;
+
+    const V0: &str = r#"{
+        "os": "linux",
+        "cpu": 12,
+        "memory": 4294967296,
+        "macAddress": "f6:db:b3:ec:f9:3f",
+        "sharing": {
+            "user": "~"
+        }
+    }"#;
+
+    const V1: &str = r#"{
+        "schemaVersion": 1,
+        "os": "linux",
+        "cpu": 2,
+        "ram": 1073741824,
+        "macAddress": "f6:db:b3:ec:f9:3f",
+        "sharing": {},
+        "rosetta": false
+    }"#;
+
+    const V2: &str = r#"{
+        "schemaVersion": 2,
+        "os": "linux",
+        "cpu": 2,
+        "ram": 1073741824,
+        "macAddress": "f6:db:b3:ec:f9:3f",
+        "sharing": {},
+        "rosetta": false,
+        "ssh": {
+            "user": "me",
+            "port": 2222,
+            "identityFile": "~/.ssh/id_ed25519"
+        }
+    }"#;
+
+    const V3: &str = r#"{
+        "schemaVersion": 3,
+        "os": "linux",
+        "cpu": 2,
+        "ram": 1073741824,
+        "macAddress": "f6:db:b3:ec:f9:3f",
+        "sharing": {},
+        "rosetta": false,
+        "ssh": {},
+        "ports": ["8080:80", "2222:22"]
+    }"#;
+
+    #[test]
+    fn migrate_v0() {
+        let mut config: Value = json::from_json(V0).unwrap();
+        assert_eq!(0, schema_version(&config));
+        assert!(migrate(&mut config).unwrap(), "v0 config should be migrated");
+        assert_eq!(SCHEMA_VERSION, schema_version(&config));
+        assert!(config.get("memory").is_none(), "memory should be renamed");
+
+        let config: VmConfig = json::from_value(config).unwrap();
+        assert_eq!(4 * 1024 * 1024 * 1024, config.ram);
+        assert_eq!(12, config.cpu);
+        assert_eq!("~", config.sharing["user"]);
+    }
+
+    #[test]
+    fn migrate_v1() {
+        let mut config: Value = json::from_json(V1).unwrap();
+        assert!(migrate(&mut config).unwrap(), "v1 config should be migrated");
+        assert_eq!(Some(&Value::Object(Map::new())), config.get("ssh"));
+
+        let config: VmConfig = json::from_value(config).unwrap();
+        assert_eq!(1024 * 1024 * 1024, config.ram);
+        assert_eq!(None, config.ssh.user);
+        assert_eq!(22, config.ssh.port());
+    }
+
+    #[test]
+    fn migrate_v2() {
+        let mut config: Value = json::from_json(V2).unwrap();
+        assert!(migrate(&mut config).unwrap(), "v2 config should be migrated");
+        assert_eq!(Some(&Value::Array(vec![])), config.get("ports"));
+
+        let config: VmConfig = json::from_value(config).unwrap();
+        assert_eq!(Some("me"), config.ssh.user.as_deref());
+        assert_eq!(2222, config.ssh.port());
+        assert_eq!(Some("~/.ssh/id_ed25519"), config.ssh.identity_file.as_deref());
+    }
+
+    #[test]
+    fn migrate_current_version() {
+        let mut config: Value = json::from_json(V3).unwrap();
+        assert!(!migrate(&mut config).unwrap(), "current config should not be migrated");
+        let config: VmConfig = json::from_value(config).unwrap();
+        assert_eq!(1024 * 1024 * 1024, config.ram);
+        assert_eq!(vec!["8080:80", "2222:22"], config.ports);
+    }
+
+    #[test]
+    fn migrate_newer_version() {
+        let mut config: Value = json::from_json(r#"{"schemaVersion": 999}"#).unwrap();
+        assert!(matches!(migrate(&mut config), Err(VzError::InvalidConfig(_))), "newer config should be rejected");
+    }
+}
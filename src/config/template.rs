@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::global_config;
+use super::vm_config::Os;
+use super::vm_config::VmConfig;
+use super::vm_dir;
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::json;
+
+// ~/.config/vz/templates/[name].json, partial vm config used by vz create, all fields are optional
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct VmTemplate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os: Option<Os>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<usize>,
+    // in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ram: Option<u64>,
+    // in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disk: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sharing: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rosetta: Option<bool>,
+}
+
+impl VmTemplate {
+    // apply values which are not covered by create arguments
+    pub fn apply(&self, config: &mut VmConfig) {
+        if let Some(sharing) = &self.sharing {
+            config.sharing.extend(sharing.iter().map(|(name, path)| (name.clone(), path.clone())));
+        }
+        if self.rosetta.is_some() {
+            config.rosetta = self.rosetta;
+        }
+    }
+}
+
+pub fn templates_dir() -> PathBuf {
+    global_config::config_dir().join("templates")
+}
+
+// name is validated same as vm name, so it can't escape templates dir, e.g. ../config
+pub fn load(name: &str) -> Result<VmTemplate> {
+    vm_dir::validate_name(name)?;
+    let path = templates_dir().join(format!("{name}.json"));
+    if !path.exists() {
+        return Err(VzError::InvalidArgument(format!("template not found, path={}", path.to_string_lossy())));
+    }
+    let json = fs::read_to_string(&path).or_io_error("failed to load template")?;
+    json::from_json(&json)
+}
+
+pub fn names() -> Vec<String> {
+    let Ok(read_dir) = templates_dir().read_dir() else {
+        return vec![];
+    };
+    let mut names: Vec<String> = read_dir
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+        .filter_map(|path| path.file_stem().map(|name| name.to_string_lossy().to_string()))
+        .filter(|name| vm_dir::validate_name(name).is_ok())
+        .collect();
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply() {
+        let template: VmTemplate =
+            json::from_json(r#"{"cpu": 4, "disk": 100000000000, "sharing": {"home": "~"}, "rosetta": true}"#).unwrap();
+        assert_eq!(Some(4), template.cpu);
+        assert_eq!(Some(100_000_000_000), template.disk);
+
+        let mut config: VmConfig = json::from_json(
+            r#"{"schemaVersion": 1, "os": "linux", "cpu": 1, "ram": 1073741824, "macAddress": "f6:db:b3:ec:f9:3f", "sharing": {"data": "/data"}, "rosetta": false}"#,
+        )
+        .unwrap();
+        template.apply(&mut config);
+        assert_eq!(Some("~"), config.sharing.get("home").map(String::as_str));
+        assert_eq!(Some("/data"), config.sharing.get("data").map(String::as_str));
+        assert_eq!(Some(true), config.rosetta);
+    }
+
+    #[test]
+    fn unknown_field() {
+        assert!(json::from_json::<VmTemplate>(r#"{"memory": 1073741824}"#).is_err(), "unknown field");
+    }
+
+    #[test]
+    fn load_invalid_name() {
+        assert!(matches!(load("../config"), Err(VzError::InvalidArgument(_))), "path traversal");
+    }
+}
@@ -0,0 +1,216 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::result;
+
+use super::vm_config::Os;
+use super::vm_config::PortForward;
+use super::vm_config::VmConfig;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::path::PathExtension as _;
+
+#[derive(Debug, PartialEq)]
+pub struct Problem {
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+// check config without virtualization framework, return all problems found
+pub fn validate(config: &VmConfig) -> Vec<Problem> {
+    let mut problems = vec![];
+    let mut problem = |field: &str, message: String| problems.push(Problem { field: field.to_owned(), message });
+
+    if config.cpu == 0 {
+        problem("cpu", "must be at least 1".to_owned());
+    }
+
+    if config.ram == 0 || !config.ram.is_multiple_of(1024 * 1024) {
+        problem("ram", format!("must be positive multiple of 1MiB, value={}", config.ram));
+    }
+
+    if let Err(message) = validate_mac_address(&config.mac_address) {
+        problem("macAddress", format!("{message}, value={}", config.mac_address));
+    }
+
+    let mut sharing: Vec<(&String, &String)> = config.sharing.iter().collect();
+    sharing.sort();
+    for (name, path) in sharing {
+        let field = format!("sharing.{name}");
+        if name.is_empty() {
+            problem(&field, "name must not be empty".to_owned());
+        }
+        let absolute_path = PathBuf::from(path).to_absolute_path();
+        if !absolute_path.is_dir() {
+            problem(&field, format!("directory does not exist, path={}", absolute_path.to_string_lossy()));
+        }
+    }
+
+    if config.ssh.port == Some(0) {
+        problem("ssh.port", "must be between 1 and 65535".to_owned());
+    }
+
+    let mut host_ports = vec![];
+    for port in &config.ports {
+        match PortForward::parse(port) {
+            Some(forward) if host_ports.contains(&forward.host_port) => {
+                problem("ports", format!("host port is forwarded more than once, value={port}"));
+            }
+            Some(forward) => host_ports.push(forward.host_port),
+            None => problem("ports", format!("must be host_port:guest_port, e.g. 8080:80, value={port}")),
+        }
+    }
+
+    match config.os {
+        Os::Linux => {}
+        Os::MacOs => {
+            if let Some(true) = config.rosetta {
+                problem("rosetta", "only supported by linux vm".to_owned());
+            }
+            for (field, value) in
+                [("hardware_model", &config.hardware_model), ("machine_identifier", &config.machine_identifier)]
+            {
+                match value {
+                    None => problem(field, "required for macOS vm".to_owned()),
+                    Some(value) if !is_base64(value) => problem(field, "must be base64 encoded".to_owned()),
+                    Some(_) => {}
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+// ssh settings are only used by vz ssh, they must not prevent vm from running
+pub fn validate_ssh(config: &VmConfig) -> Vec<Problem> {
+    let mut problems = vec![];
+    if let Some(identity_file) = &config.ssh.identity_file
+        && !PathBuf::from(identity_file).to_absolute_path().is_file()
+    {
+        problems.push(Problem {
+            field: "ssh.identityFile".to_owned(),
+            message: format!("file does not exist, path={identity_file}"),
+        });
+    }
+    problems
+}
+
+// validate and convert problems into error
+pub fn check(config: &VmConfig) -> Result<()> {
+    into_result(&validate(config))
+}
+
+pub fn check_ssh(config: &VmConfig) -> Result<()> {
+    into_result(&validate_ssh(config))
+}
+
+fn into_result(problems: &[Problem]) -> Result<()> {
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let problems: Vec<String> = problems.iter().map(Problem::to_string).collect();
+    Err(VzError::InvalidConfig(problems.join("; ")))
+}
+
+fn validate_mac_address(mac_address: &str) -> result::Result<(), &'static str> {
+    let octets: Vec<&str> = mac_address.split(':').collect();
+    if octets.len() != 6 || octets.iter().any(|octet| octet.len() != 2 || !octet.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return Err("must be 6 colon separated hex octets, e.g. f6:db:b3:ec:f9:3f");
+    }
+    let first_octet = octets.first().and_then(|octet| u8::from_str_radix(octet, 16).ok());
+    if first_octet.is_some_and(|octet| octet & 1 == 1) {
+        return Err("must be unicast address");
+    }
+    Ok(())
+}
+
+fn is_base64(value: &str) -> bool {
+    let data = value.trim_end_matches('=');
+    !value.is_empty()
+        && value.len().is_multiple_of(4)
+        && value.len() - data.len() <= 2
+        && data.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::config::migration;
+    use crate::config::vm_config::Ssh;
+
+    fn config() -> VmConfig {
+        VmConfig {
+            schema_version: migration::SCHEMA_VERSION,
+            os: Os::Linux,
+            cpu: 1,
+            ram: 1024 * 1024 * 1024,
+            mac_address: "f6:db:b3:ec:f9:3f".to_owned(),
+            sharing: HashMap::from([("home".to_owned(), "~".to_owned())]),
+            rosetta: Some(false),
+            hardware_model: None,
+            machine_identifier: None,
+            ssh: Ssh::default(),
+            ports: vec![],
+        }
+    }
+
+    #[test]
+    fn validate_valid_config() {
+        assert_eq!(Vec::<Problem>::new(), validate(&config()));
+    }
+
+    #[test]
+    fn validate_invalid_config() {
+        let mut config = config();
+        config.cpu = 0;
+        config.ram = 1024 * 1024 + 1;
+        config.mac_address = "f6:db:b:ec:f9:3f".to_owned();
+        config.sharing.insert("missing".to_owned(), "/not/exist".to_owned());
+        config.ssh = Ssh { user: None, port: Some(0), identity_file: Some("/not/exist".to_owned()) };
+        config.ports = vec!["8080:80".to_owned(), "8080:8080".to_owned(), "80".to_owned(), "0:80".to_owned()];
+
+        let fields: Vec<String> = validate(&config).into_iter().map(|problem| problem.field).collect();
+        assert_eq!(vec!["cpu", "ram", "macAddress", "sharing.missing", "ssh.port", "ports", "ports", "ports"], fields);
+    }
+
+    #[test]
+    fn validate_ssh_config() {
+        let mut config = config();
+        assert_eq!(Vec::<Problem>::new(), validate_ssh(&config));
+
+        config.ssh.identity_file = Some("/not/exist".to_owned());
+        assert_eq!(Vec::<Problem>::new(), validate(&config), "missing identity file must not prevent vm from running");
+        let fields: Vec<String> = validate_ssh(&config).into_iter().map(|problem| problem.field).collect();
+        assert_eq!(vec!["ssh.identityFile"], fields);
+        assert!(check_ssh(&config).is_err());
+    }
+
+    #[test]
+    fn validate_mac_os_config() {
+        let mut config = config();
+        config.os = Os::MacOs;
+        config.rosetta = Some(true);
+        config.hardware_model = Some("YnBsaXN0".to_owned());
+        config.machine_identifier = Some("not base64!".to_owned());
+
+        let fields: Vec<String> = validate(&config).into_iter().map(|problem| problem.field).collect();
+        assert_eq!(vec!["rosetta", "machine_identifier"], fields);
+    }
+
+    #[test]
+    fn mac_address() {
+        assert!(validate_mac_address("f6:db:b3:ec:f9:3f").is_ok(), "valid mac address");
+        assert!(validate_mac_address("f7:db:b3:ec:f9:3f").is_err(), "multicast mac address");
+        assert!(validate_mac_address("f6:db:b3:ec:f9").is_err(), "too short");
+        assert!(validate_mac_address("f6:db:b3:ec:f9:zz").is_err(), "not hex");
+    }
+}
@@ -17,6 +17,8 @@ use objc2_virtualization::VZVirtioNetworkDeviceConfiguration;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::error::Result;
+use crate::error::VzError;
 use crate::util::path::PathExtension as _;
 
 #[derive(Serialize, Deserialize, Debug, Clone, clap::ValueEnum)]
@@ -31,6 +33,8 @@ pub enum Os {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct VmConfig {
+    #[serde(rename = "schemaVersion")]
+    pub schema_version: u64,
     pub os: Os,
     pub cpu: usize,
     pub ram: u64,
@@ -43,22 +47,58 @@ pub struct VmConfig {
     pub hardware_model: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub machine_identifier: Option<String>,
+    #[serde(default)]
+    pub ssh: Ssh,
+    // forward localhost port to guest, e.g. "8080:80"
+    #[serde(default)]
+    pub ports: Vec<String>,
 }
 
-pub fn network(config: &VmConfig) -> Retained<VZNetworkDeviceConfiguration> {
+pub struct PortForward {
+    pub host_port: u16,
+    pub guest_port: u16,
+}
+
+impl PortForward {
+    // host_port:guest_port
+    pub fn parse(value: &str) -> Option<PortForward> {
+        let (host_port, guest_port) = value.split_once(':')?;
+        let port = |port: &str| port.parse::<u16>().ok().filter(|port| *port != 0);
+        Some(PortForward { host_port: port(host_port)?, guest_port: port(guest_port)? })
+    }
+}
+
+// used by vz ssh and vz ssh-config, unset values fall back to ssh defaults
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Ssh {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(rename = "identityFile", skip_serializing_if = "Option::is_none")]
+    pub identity_file: Option<String>,
+}
+
+impl Ssh {
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(22)
+    }
+}
+
+pub fn network(config: &VmConfig) -> Result<Retained<VZNetworkDeviceConfiguration>> {
     unsafe {
         let network = VZVirtioNetworkDeviceConfiguration::new();
         network.setAttachment(Some(&VZNATNetworkDeviceAttachment::new()));
-        let mac_address =
-            VZMACAddress::initWithString(VZMACAddress::alloc(), &NSString::from_str(&config.mac_address)).unwrap();
+        let mac_address = VZMACAddress::initWithString(VZMACAddress::alloc(), &NSString::from_str(&config.mac_address))
+            .ok_or_else(|| VzError::InvalidConfig(format!("invalid macAddress, value={}", config.mac_address)))?;
         network.setMACAddress(&mac_address);
-        Retained::into_super(network)
+        Ok(Retained::into_super(network))
     }
 }
 
-pub fn sharing_directories(config: &VmConfig) -> Option<Retained<VZDirectorySharingDeviceConfiguration>> {
+pub fn sharing_directories(config: &VmConfig) -> Result<Option<Retained<VZDirectorySharingDeviceConfiguration>>> {
     if config.sharing.is_empty() {
-        return None;
+        return Ok(None);
     }
     let mut keys: Vec<Retained<NSString>> = vec![];
     let mut values: Vec<Retained<VZSharedDirectory>> = vec![];
@@ -66,7 +106,12 @@ pub fn sharing_directories(config: &VmConfig) -> Option<Retained<VZDirectoryShar
     for (key, value) in &config.sharing {
         keys.push(NSString::from_str(key));
         let path = PathBuf::from(value).to_absolute_path();
-        assert!(path.exists(), "sharing path does not exist, name={key}, path={}", path.to_string_lossy());
+        if !path.exists() {
+            return Err(VzError::InvalidConfig(format!(
+                "sharing path does not exist, name={key}, path={}",
+                path.to_string_lossy()
+            )));
+        }
         unsafe {
             values.push(VZSharedDirectory::initWithURL_readOnly(VZSharedDirectory::alloc(), &path.to_ns_url(), false));
         }
@@ -81,6 +126,6 @@ pub fn sharing_directories(config: &VmConfig) -> Option<Retained<VZDirectoryShar
         );
         let sharings = VZMultipleDirectoryShare::initWithDirectories(VZMultipleDirectoryShare::alloc(), &directories);
         device.setShare(Some(&Retained::into_super(sharings)));
-        Some(Retained::into_super(device))
+        Ok(Some(Retained::into_super(device)))
     }
 }
@@ -1,11 +1,20 @@
+use std::env;
 use std::fs;
+use std::io;
+use std::path::Path;
 use std::path::PathBuf;
 
 use libc::pid_t;
+use serde_json::Value;
 use tracing::info;
 use uuid::Uuid;
 
+use super::migration;
 use super::vm_config::VmConfig;
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::file_lock::DirLock;
 use crate::util::file_lock::FileLock;
 use crate::util::json;
 use crate::util::path::PathExtension as _;
@@ -15,6 +24,12 @@ pub struct VmDir {
     pub nvram_path: PathBuf,
     pub disk_path: PathBuf,
     pub config_path: PathBuf,
+    // cloud-init seed, attached until first boot completes
+    pub seed_path: PathBuf,
+    // built from run --attach-dir, regenerated on every run
+    pub attach_path: PathBuf,
+    // guest host keys pinned by vz ssh, ip may be reused by other vm
+    pub known_hosts_path: PathBuf,
 }
 
 impl VmDir {
@@ -22,7 +37,10 @@ impl VmDir {
         let nvram_path = dir.as_path().join("nvram.bin");
         let disk_path = dir.as_path().join("disk.img");
         let config_path = dir.as_path().join("config.json");
-        VmDir { dir, nvram_path, disk_path, config_path }
+        let seed_path = dir.as_path().join("seed.iso");
+        let attach_path = dir.as_path().join("attach.iso");
+        let known_hosts_path = dir.as_path().join("known_hosts");
+        VmDir { dir, nvram_path, disk_path, config_path, seed_path, attach_path, known_hosts_path }
     }
 
     pub fn name(&self) -> String {
@@ -33,56 +51,290 @@ impl VmDir {
         self.config_path.exists() && self.disk_path.exists() && self.nvram_path.exists()
     }
 
-    pub fn load_config(&self) -> VmConfig {
-        let json =
-            fs::read_to_string(&self.config_path).unwrap_or_else(|err| panic!("failed to load config, err={err}"));
-        json::from_json(&json)
+    // config.json with older schema version will be upgraded in place, original file is kept as config.json.bak
+    pub fn load_config(&self) -> Result<VmConfig> {
+        let json = fs::read_to_string(&self.config_path).or_io_error("failed to load config")?;
+        let (config, migrated) = self.parse_config(&json)?;
+        if migrated {
+            self.save_config(&config)?;
+        }
+        Ok(config)
+    }
+
+    pub fn save_config(&self, config: &VmConfig) -> Result<()> {
+        let json = json::to_json_pretty(&config);
+        fs::write(&self.config_path, json).or_io_error("failed to save config")
     }
 
-    pub fn save_config(&self, config: &VmConfig) {
+    // config.json is lock file, while lock is held, it must be read / written via lock, refer to FileLock
+    pub fn load_config_locked(&self, lock: &FileLock) -> Result<VmConfig> {
+        let json = lock.read().or_io_error("failed to load config")?;
+        let (config, migrated) = self.parse_config(&json)?;
+        if migrated {
+            Self::save_config_locked(lock, &config)?;
+        }
+        Ok(config)
+    }
+
+    pub fn save_config_locked(lock: &FileLock, config: &VmConfig) -> Result<()> {
         let json = json::to_json_pretty(&config);
-        fs::write(&self.config_path, json).unwrap_or_else(|err| panic!("failed to save config, err={err}"));
+        lock.write(json.as_bytes()).or_io_error("failed to save config")
     }
 
-    pub fn resize(&self, size: u64) {
+    // return config and whether it's migrated
+    fn parse_config(&self, json: &str) -> Result<(VmConfig, bool)> {
+        let mut value: Value = json::from_json(json)?;
+        let version = migration::schema_version(&value);
+        if !migration::migrate(&mut value)? {
+            return Ok((json::from_value(value)?, false));
+        }
+
+        let backup_path = self.dir.join("config.json.bak");
+        info!(
+            "migrate config, from={version}, to={}, backup={}",
+            migration::SCHEMA_VERSION,
+            backup_path.to_string_lossy()
+        );
+        fs::write(&backup_path, json).or_io_error("failed to backup config")?;
+        Ok((json::from_value(value)?, true))
+    }
+
+    pub fn resize(&self, size: u64) -> Result<()> {
         let file = fs::OpenOptions::new()
             .create(true)
             .append(true)
             .open(&self.disk_path)
-            .unwrap_or_else(|err| panic!("failed to open file, err={err}"));
-        file.set_len(size).unwrap_or_else(|err| panic!("failed to resize file, err={err}"));
+            .or_io_error("failed to open file")?;
+        file.set_len(size).or_io_error("failed to resize file")
     }
 
-    pub fn lock(&self) -> FileLock {
+    pub fn lock(&self) -> Result<FileLock> {
         let lock = FileLock::new(&self.config_path);
-        if lock.lock() { lock } else { panic!("vm is already running, name={}", self.name()) }
+        if lock.lock() { Ok(lock) } else { Err(VzError::LockHeld(self.name())) }
+    }
+
+    pub fn ensure_stopped(&self) -> Result<()> {
+        if self.pid().is_some() { Err(VzError::VmRunning(self.name())) } else { Ok(()) }
+    }
+
+    pub fn snapshots_dir(&self) -> PathBuf {
+        self.dir.join("snapshots")
+    }
+
+    // snapshot has same layout as vm dir, plus snapshot.json
+    pub fn snapshot_dir(&self, tag: &str) -> VmDir {
+        VmDir::new(self.snapshots_dir().join(tag))
+    }
+
+    pub fn snapshot_dirs(&self) -> Vec<VmDir> {
+        list_dirs(&self.snapshots_dir())
     }
 
     pub fn pid(&self) -> Option<pid_t> {
         let lock = FileLock::new(&self.config_path);
         lock.pid()
     }
+
+    // rename to temp dir first, so partially deleted dir is never seen as vm,
+    // then remove config.json first, so remains are uninitialized and collected by gc if removal fails
+    pub fn remove(&self) -> Result<()> {
+        let _lock = self.lock()?;
+        let trash_dir = VmDir::new(self.dir.with_file_name(Uuid::now_v7().to_string()));
+        info!("move vm dir to trash, from={}, to={}", self.dir.to_string_lossy(), trash_dir.dir.to_string_lossy());
+        fs::rename(&self.dir, &trash_dir.dir).or_io_error("failed to rename dir")?;
+        fs::remove_file(&trash_dir.config_path).or_io_error("failed to remove config")?;
+        fs::remove_dir_all(&trash_dir.dir).or_io_error("failed to remove dir")
+    }
 }
 
+// VZ_HOME overrides default home dir, e.g. to keep vms on external volume
 pub fn home_dir() -> PathBuf {
-    PathBuf::from("~/.local/share/vz").to_absolute_path()
+    let home_dir = env::var("VZ_HOME").unwrap_or_else(|_| "~/.local/share/vz".to_owned());
+    PathBuf::from(home_dir).to_absolute_path()
 }
 
 pub fn vm_dir(name: &str) -> VmDir {
     VmDir::new(home_dir().join(name))
 }
 
+pub fn initialized_vm_dir(name: &str) -> Result<VmDir> {
+    let dir = vm_dir(name);
+    if dir.initialized() { Ok(dir) } else { Err(VzError::ConfigNotFound(name.to_owned())) }
+}
+
 pub fn vm_dirs() -> Vec<VmDir> {
-    if let Ok(read_dir) = home_dir().read_dir() {
-        read_dir.into_iter().flatten().map(|dir| VmDir::new(dir.path())).filter(VmDir::initialized).collect()
+    list_dirs(&home_dir())
+}
+
+fn list_dirs(path: &Path) -> Vec<VmDir> {
+    if let Ok(read_dir) = path.read_dir() {
+        let mut dirs: Vec<VmDir> = read_dir
+            .into_iter()
+            .flatten()
+            .map(|dir| VmDir::new(dir.path()))
+            .filter(|dir| dir.initialized() && !is_temp_dir(dir))
+            .collect();
+        dirs.sort_by(|dir1, dir2| dir1.dir.cmp(&dir2.dir));
+        dirs
     } else {
         vec![]
     }
 }
 
-pub fn create_temp_vm_dir() -> VmDir {
+// vm name and snapshot tag are used as dir name, uuid is reserved for temp dirs
+pub fn validate_name(name: &str) -> Result<()> {
+    let valid = name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && Uuid::parse_str(name).is_err();
+    if valid {
+        Ok(())
+    } else {
+        Err(VzError::InvalidArgument(format!(
+            "invalid name, must start with letter or digit, and only contain letters, digits, '-', '_' or '.', name={name}"
+        )))
+    }
+}
+
+// temp dirs are named by uuid, e.g. vm being created or deleted
+fn is_temp_dir(dir: &VmDir) -> bool {
+    Uuid::parse_str(&dir.name()).is_ok()
+}
+
+// temp dirs left by interrupted create, import or rm, regardless whether initialized
+pub fn temp_vm_dirs() -> Vec<VmDir> {
+    let Ok(read_dir) = home_dir().read_dir() else {
+        return vec![];
+    };
+    let mut dirs: Vec<VmDir> = read_dir
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| VmDir::new(entry.path()))
+        .filter(is_temp_dir)
+        .collect();
+    dirs.sort_by(|dir1, dir2| dir1.dir.cmp(&dir2.dir));
+    dirs
+}
+
+// seconds since epoch, uuid v7 contains creation time
+pub fn temp_vm_dir_created_at(dir: &VmDir) -> Option<u64> {
+    let uuid = Uuid::parse_str(&dir.name()).ok()?;
+    uuid.get_timestamp().map(|timestamp| timestamp.to_unix().0)
+}
+
+// lock must be held while writing temp dir, so gc skips it
+pub fn create_temp_vm_dir() -> Result<(VmDir, DirLock)> {
     let temp_dir = home_dir().join(Uuid::now_v7().to_string());
     info!("create temp vm dir, dir={}", temp_dir.to_string_lossy());
-    fs::create_dir_all(&temp_dir).unwrap_or_else(|err| panic!("failed to create temp vm dir, err={err}"));
-    VmDir::new(temp_dir)
+    fs::create_dir_all(&temp_dir).or_io_error("failed to create temp vm dir")?;
+    let lock = DirLock::lock(&temp_dir).ok_or_else(|| {
+        VzError::Io(
+            format!("failed to lock temp vm dir, dir={}", temp_dir.to_string_lossy()),
+            io::Error::last_os_error(),
+        )
+    })?;
+    Ok((VmDir::new(temp_dir), lock))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use super::*;
+    use crate::util::time;
+
+    #[test]
+    fn load_config_with_migration() {
+        let dir = VmDir::new(env::temp_dir().join(Uuid::now_v7().to_string()));
+        fs::create_dir_all(&dir.dir).unwrap();
+        let json =
+            r#"{"os": "linux", "cpu": 1, "memory": 1073741824, "macAddress": "f6:db:b3:ec:f9:3f", "sharing": {}}"#;
+        fs::write(&dir.config_path, json).unwrap();
+
+        let config = dir.load_config().unwrap();
+        assert_eq!(migration::SCHEMA_VERSION, config.schema_version);
+        assert_eq!(1024 * 1024 * 1024, config.ram);
+        assert_eq!(json, fs::read_to_string(dir.dir.join("config.json.bak")).unwrap());
+
+        let saved: Value = json::from_json(&fs::read_to_string(&dir.config_path).unwrap()).unwrap();
+        assert_eq!(migration::SCHEMA_VERSION, migration::schema_version(&saved));
+
+        fs::remove_dir_all(&dir.dir).unwrap();
+    }
+
+    #[test]
+    fn load_and_save_config_locked() {
+        let dir = VmDir::new(env::temp_dir().join(Uuid::now_v7().to_string()));
+        fs::create_dir_all(&dir.dir).unwrap();
+        let json =
+            r#"{"os": "linux", "cpu": 1, "memory": 1073741824, "macAddress": "f6:db:b3:ec:f9:3f", "sharing": {}}"#;
+        fs::write(&dir.config_path, json).unwrap();
+
+        let lock = dir.lock().unwrap();
+        let mut config = dir.load_config_locked(&lock).unwrap();
+        assert_eq!(migration::SCHEMA_VERSION, config.schema_version);
+        config.cpu = 2;
+        VmDir::save_config_locked(&lock, &config).unwrap();
+        assert!(locked_by_other_process(&dir.config_path), "lock must be held after load and save");
+        drop(lock);
+        assert!(!locked_by_other_process(&dir.config_path), "lock is released");
+        assert_eq!(2, dir.load_config().unwrap().cpu);
+
+        fs::remove_dir_all(&dir.dir).unwrap();
+    }
+
+    // fcntl lock is per process, F_GETLK doesn't report locks held by current process, so check from child process
+    fn locked_by_other_process(path: &Path) -> bool {
+        let path = CString::new(path.to_string_lossy().as_bytes()).unwrap();
+        unsafe {
+            let pid = libc::fork();
+            if pid == 0 {
+                let fd = libc::open(path.as_ptr(), libc::O_RDONLY);
+                let mut lock = libc::flock {
+                    l_start: 0,
+                    l_len: 0,
+                    l_pid: -1,
+                    l_type: libc::F_RDLCK,
+                    l_whence: libc::SEEK_SET as libc::c_short,
+                };
+                libc::fcntl(fd, libc::F_GETLK, &mut lock);
+                libc::_exit(if lock.l_type == libc::F_WRLCK { 0 } else { 1 });
+            }
+            let mut status = 0;
+            libc::waitpid(pid, &raw mut status, 0);
+            libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0
+        }
+    }
+
+    #[test]
+    fn validate_name() {
+        assert!(super::validate_name("debian").is_ok(), "valid name");
+        assert!(super::validate_name("docker-host_1.2").is_ok(), "valid name");
+        assert!(super::validate_name("").is_err(), "empty");
+        assert!(super::validate_name(".hidden").is_err(), "leading dot");
+        assert!(super::validate_name("..").is_err(), "parent dir");
+        assert!(super::validate_name("a/b").is_err(), "slash");
+        assert!(super::validate_name("my vm").is_err(), "space");
+        assert!(super::validate_name(&Uuid::now_v7().to_string()).is_err(), "uuid");
+    }
+
+    #[test]
+    fn temp_vm_dir_created_at() {
+        let dir = VmDir::new(env::temp_dir().join(Uuid::now_v7().to_string()));
+        let created_at = super::temp_vm_dir_created_at(&dir).unwrap();
+        assert!(time::now().abs_diff(created_at) <= 1, "created_at must be current time");
+        assert_eq!(None, super::temp_vm_dir_created_at(&VmDir::new(PathBuf::from("debian"))));
+    }
+
+    #[test]
+    fn remove() {
+        let dir = VmDir::new(env::temp_dir().join(Uuid::now_v7().to_string()).join("vm"));
+        fs::create_dir_all(&dir.dir).unwrap();
+        fs::write(&dir.config_path, "{}").unwrap();
+
+        dir.remove().unwrap();
+        assert!(!dir.dir.exists());
+        assert_eq!(0, dir.dir.parent().unwrap().read_dir().unwrap().count());
+
+        fs::remove_dir_all(dir.dir.parent().unwrap()).unwrap();
+    }
 }
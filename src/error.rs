@@ -0,0 +1,78 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::result;
+
+pub type Result<T> = result::Result<T, VzError>;
+
+#[derive(Debug)]
+pub enum VzError {
+    InvalidArgument(String),
+    ConfigNotFound(String),
+    InvalidConfig(String),
+    VmRunning(String),
+    VmNotRunning(String),
+    LockHeld(String),
+    Timeout(String),
+    Io(String, io::Error),
+    Framework(String),
+}
+
+impl VzError {
+    // exit codes are part of cli contract (documented in README.md), never renumber existing ones
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            VzError::InvalidArgument(_) => 2,
+            VzError::ConfigNotFound(_) => 3,
+            VzError::InvalidConfig(_) => 4,
+            VzError::VmRunning(_) => 5,
+            VzError::VmNotRunning(_) => 6,
+            VzError::LockHeld(_) => 7,
+            VzError::Timeout(_) => 8,
+            VzError::Io(_, _) => 9,
+            VzError::Framework(_) => 10,
+        }
+    }
+}
+
+impl fmt::Display for VzError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VzError::InvalidArgument(message) | VzError::Timeout(message) | VzError::Framework(message) => {
+                write!(f, "{message}")
+            }
+            VzError::ConfigNotFound(name) => write!(f, "vm not found, name={name}"),
+            VzError::InvalidConfig(message) => write!(f, "invalid config, {message}"),
+            VzError::VmRunning(name) => write!(f, "vm is running, name={name}"),
+            VzError::VmNotRunning(name) => write!(f, "vm is not running, name={name}"),
+            VzError::LockHeld(name) => write!(f, "vm is locked by another process, name={name}"),
+            VzError::Io(message, err) => write!(f, "{message}, err={err}"),
+        }
+    }
+}
+
+impl Error for VzError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            VzError::Io(_, err) => Some(err),
+            VzError::InvalidArgument(_)
+            | VzError::ConfigNotFound(_)
+            | VzError::InvalidConfig(_)
+            | VzError::VmRunning(_)
+            | VzError::VmNotRunning(_)
+            | VzError::LockHeld(_)
+            | VzError::Timeout(_)
+            | VzError::Framework(_) => None,
+        }
+    }
+}
+
+pub trait IoResultExtension<T> {
+    fn or_io_error(self, message: &str) -> Result<T>;
+}
+
+impl<T> IoResultExtension<T> for io::Result<T> {
+    fn or_io_error(self, message: &str) -> Result<T> {
+        self.map_err(|err| VzError::Io(message.to_owned(), err))
+    }
+}
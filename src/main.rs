@@ -1,14 +1,35 @@
+use std::io;
+use std::process;
+
 use clap::Parser;
 use clap::Subcommand;
+use command::clone::CloneVm;
 use command::complete::Complete;
 use command::completion::Completion;
+use command::config::Config;
 use command::create::Create;
+use command::disk::Disk;
 use command::edit::Edit;
+use command::export::Export;
+use command::gc::Gc;
+use command::import::Import;
+use command::inspect::Inspect;
 use command::install::Install;
+use command::ip::Ip;
 use command::ipsw::Ipsw;
+use command::iso::Iso;
 use command::list::List;
+use command::remove::Remove;
+use command::rename::Rename;
 use command::run::Run;
+use command::snapshot::Snapshot;
+use command::ssh::Ssh;
+use command::ssh_config::SshConfig;
 use command::stop::Stop;
+use command::template::Template;
+use command::validate::Validate;
+use config::global_config;
+use tracing::error;
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::Layer as _;
 use tracing_subscriber::fmt;
@@ -17,6 +38,7 @@ use tracing_subscriber::util::SubscriberInitExt as _;
 
 mod command;
 mod config;
+mod error;
 mod util;
 mod vm;
 
@@ -35,10 +57,26 @@ pub enum Command {
     List(List),
     #[command(about = "create vm")]
     Create(Create),
+    #[command(about = "clone vm with new mac address and machine identifier")]
+    Clone(CloneVm),
+    #[command(about = "export vm as tar.zst archive")]
+    Export(Export),
+    #[command(about = "import vm from archive created by export")]
+    Import(Import),
+    #[command(name = "rm", about = "remove vm")]
+    Remove(Remove),
+    #[command(name = "mv", about = "rename vm")]
+    Rename(Rename),
     #[command(about = "run vm")]
     Run(Run),
     #[command(about = "stop vm")]
     Stop(Stop),
+    #[command(about = "print vm ip, wait until assigned with --wait")]
+    Ip(Ip),
+    #[command(about = "ssh into vm, e.g. vz ssh debian -- uname -a")]
+    Ssh(Ssh),
+    #[command(name = "ssh-config", about = "print ssh config Host block of all vms")]
+    SshConfig(SshConfig),
     #[command(
         about = "get macOS restore image ipsw url",
         long_about = "get macOS restore image ipsw url, download ipsw file manually, then use in create command with --ipsw"
@@ -46,8 +84,24 @@ pub enum Command {
     Ipsw(Ipsw),
     #[command(about = "edit vm (cpu, ram, increase disk image size)")]
     Edit(Edit),
+    #[command(about = "show info, compact, shrink, import or export vm disk")]
+    Disk(Disk),
+    #[command(about = "build iso image from dir")]
+    Iso(Iso),
+    #[command(about = "get or set vm config values")]
+    Config(Config),
+    #[command(about = "create, list, restore or remove offline vm snapshots")]
+    Snapshot(Snapshot),
     #[command(about = "install macOS")]
     Install(Install),
+    #[command(about = "show vm details in json")]
+    Inspect(Inspect),
+    #[command(about = "validate vm config")]
+    Validate(Validate),
+    #[command(about = "list or show vm templates")]
+    Template(Template),
+    #[command(about = "remove orphaned temp vm dirs left by interrupted create, import or rm")]
+    Gc(Gc),
     #[command(about = "generate shell completion")]
     Completion(Completion),
     #[command(name = "_complete", hide = true)]
@@ -56,19 +110,53 @@ pub enum Command {
 
 fn main() {
     tracing_subscriber::registry()
-        .with(fmt::layer().compact().with_line_number(true).with_thread_ids(true).with_filter(LevelFilter::INFO))
+        .with(
+            fmt::layer()
+                .compact()
+                .with_writer(io::stderr)
+                .with_line_number(true)
+                .with_thread_ids(true)
+                .with_filter(LevelFilter::INFO),
+        )
         .init();
 
     let cli = Cli::parse();
-    match cli.command {
-        Command::List(_) => List::execute(),
+    if let Err(err) = global_config::load() {
+        error!("{err}");
+        process::exit(err.exit_code());
+    }
+    let result = match cli.command {
+        Command::List(command) => command.execute(),
         Command::Create(command) => command.execute(),
+        Command::Clone(command) => command.execute(),
+        Command::Export(command) => command.execute(),
+        Command::Import(command) => command.execute(),
+        Command::Remove(command) => command.execute(),
+        Command::Rename(command) => command.execute(),
         Command::Run(command) => command.execute(),
         Command::Stop(command) => command.execute(),
+        Command::Ip(command) => command.execute(),
+        Command::Ssh(command) => command.execute(),
+        Command::SshConfig(_) => SshConfig::execute(),
         Command::Ipsw(_) => Ipsw::execute(),
         Command::Edit(command) => command.execute(),
+        Command::Disk(command) => command.execute(),
+        Command::Iso(command) => command.execute(),
+        Command::Config(command) => command.execute(),
+        Command::Snapshot(command) => command.execute(),
         Command::Install(command) => command.execute(),
-        Command::Complete(command) => command.execute(),
+        Command::Inspect(command) => command.execute(),
+        Command::Validate(command) => command.execute(),
+        Command::Template(command) => command.execute(),
+        Command::Gc(command) => command.execute(),
+        Command::Complete(command) => {
+            command.execute();
+            Ok(())
+        }
         Command::Completion(_) => Completion::execute(),
+    };
+    if let Err(err) = result {
+        error!("{err}");
+        process::exit(err.exit_code());
     }
 }
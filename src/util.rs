@@ -1,3 +1,22 @@
+pub mod bytes;
+pub mod dhcpd_leases;
+pub mod disk_image;
 pub mod file_lock;
+pub mod gpt;
+pub mod guest_ip;
+pub mod hosts;
+pub mod inflate;
+pub mod iso9660;
 pub mod json;
 pub mod path;
+pub mod qcow2;
+pub mod sha256;
+pub mod size;
+pub mod sparse_file;
+pub mod table;
+pub mod tar;
+pub mod tcp_proxy;
+pub mod time;
+pub mod vmdk;
+pub mod yaml;
+pub mod zstd;
@@ -0,0 +1,64 @@
+// integer fields of disk / image formats at arbitrary offset and width, e.g. 4 byte sizes or 2 byte counts,
+// out of range offset reads as zero, and put ignores bytes beyond end, so malformed input never panics
+
+pub fn le_u16(bytes: &[u8], offset: usize) -> u16 {
+    let value = bytes.get(offset..offset + 2).unwrap_or_default();
+    value.iter().rev().fold(0, |value, byte| (value << 8) | u16::from(*byte))
+}
+
+pub fn le_u32(bytes: &[u8], offset: usize) -> u32 {
+    let value = bytes.get(offset..offset + 4).unwrap_or_default();
+    value.iter().rev().fold(0, |value, byte| (value << 8) | u32::from(*byte))
+}
+
+pub fn le_u64(bytes: &[u8], offset: usize) -> u64 {
+    let value = bytes.get(offset..offset + 8).unwrap_or_default();
+    value.iter().rev().fold(0, |value, byte| (value << 8) | u64::from(*byte))
+}
+
+pub fn be_u32(bytes: &[u8], offset: usize) -> u32 {
+    let value = bytes.get(offset..offset + 4).unwrap_or_default();
+    value.iter().fold(0, |value, byte| (value << 8) | u32::from(*byte))
+}
+
+pub fn be_u64(bytes: &[u8], offset: usize) -> u64 {
+    let value = bytes.get(offset..offset + 8).unwrap_or_default();
+    value.iter().fold(0, |value, byte| (value << 8) | u64::from(*byte))
+}
+
+// lowest length bytes of value
+pub fn le_bytes(value: u64, length: usize) -> Vec<u8> {
+    (0..length).map(|index| (value >> (index * 8)) as u8).collect()
+}
+
+pub fn be_bytes(value: u64, length: usize) -> Vec<u8> {
+    (0..length).rev().map(|index| (value >> (index * 8)) as u8).collect()
+}
+
+pub fn put(bytes: &mut [u8], offset: usize, value: &[u8]) {
+    for (target, byte) in bytes.iter_mut().skip(offset).zip(value) {
+        *target = *byte;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_write() {
+        let mut bytes = vec![0; 12];
+        put(&mut bytes, 0, &le_bytes(0x0102_0304, 4));
+        put(&mut bytes, 4, &be_bytes(0x0102_0304_0506_0708, 8));
+        assert_eq!([4, 3, 2, 1, 1, 2, 3, 4, 5, 6, 7, 8], bytes.as_slice());
+        assert_eq!(0x0102, le_u16(&bytes, 2));
+        assert_eq!(0x0102_0304, le_u32(&bytes, 0));
+        assert_eq!(0x0102_0304, be_u32(&bytes, 4));
+        assert_eq!(0x0102_0304_0506_0708, be_u64(&bytes, 4));
+        assert_eq!(0x0807_0605_0403_0201, le_u64(&bytes, 4));
+        assert_eq!(0, le_u64(&bytes, 8), "out of range");
+
+        put(&mut bytes, 10, b"abc");
+        assert_eq!(b"ab", &bytes[10..]);
+    }
+}
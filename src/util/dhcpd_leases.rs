@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+
+struct Lease {
+    ip_address: String,
+    hw_address: String,
+    // expire time in unix seconds
+    expire_at: u64,
+}
+
+// read mac => ip from bootpd lease file, the newest lease wins if mac has multiple leases, missing file means no lease
+pub fn read(path: &Path) -> Result<HashMap<String, String>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(err) => return Err(err).or_io_error("failed to read dhcpd leases"),
+    };
+    Ok(parse(&content))
+}
+
+// e.g.
+// {
+//     name=debian
+//     ip_address=192.168.64.3
+//     hw_address=1,fa:5d:b:89:61:16
+//     identifier=1,fa:5d:b:89:61:16
+//     lease=0x6523b2a5
+// }
+fn parse(content: &str) -> HashMap<String, String> {
+    let mut leases: HashMap<String, Lease> = HashMap::new();
+    let mut fields: HashMap<&str, &str> = HashMap::new();
+    for line in content.lines().map(str::trim) {
+        match line {
+            "{" => fields.clear(),
+            "}" => {
+                if let Some(lease) = lease(&fields)
+                    && leases.get(&lease.hw_address).is_none_or(|existing| existing.expire_at < lease.expire_at)
+                {
+                    leases.insert(lease.hw_address.clone(), lease);
+                }
+            }
+            _ => {
+                if let Some((key, value)) = line.split_once('=') {
+                    fields.insert(key, value);
+                }
+            }
+        }
+    }
+    leases.into_values().map(|lease| (lease.hw_address, lease.ip_address)).collect()
+}
+
+fn lease(fields: &HashMap<&str, &str>) -> Option<Lease> {
+    // hardware type 1 is ethernet
+    let hw_address = fields.get("hw_address")?.strip_prefix("1,")?;
+    Some(Lease {
+        ip_address: (*fields.get("ip_address")?).to_owned(),
+        hw_address: parse_mac(hw_address),
+        expire_at: fields
+            .get("lease")
+            .and_then(|lease| u64::from_str_radix(lease.trim_start_matches("0x"), 16).ok())?,
+    })
+}
+
+// bootpd and arp both strip leading zeros, e.g. 'fa:5d:0b:89:61:16' is written as 'fa:5d:b:89:61:16'
+pub fn parse_mac(mac: &str) -> String {
+    let parts: Vec<String> =
+        mac.split(':').map(|part| if part.len() == 1 { format!("0{part}") } else { part.to_owned() }).collect();
+    parts.join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn read_leases() {
+        let path = env::temp_dir().join(Uuid::now_v7().to_string());
+        fs::write(
+            &path,
+            "{
+	name=debian
+	ip_address=192.168.64.8
+	hw_address=1,fa:5d:b:89:61:16
+	identifier=1,fa:5d:b:89:61:16
+	lease=0x6523b2a5
+}
+{
+	name=debian
+	ip_address=192.168.64.3
+	hw_address=1,fa:5d:b:89:61:16
+	identifier=1,fa:5d:b:89:61:16
+	lease=0x6523a000
+}
+{
+	name=alpine
+	ip_address=192.168.64.4
+	hw_address=1,f6:db:b3:ec:f9:3f
+	identifier=1,f6:db:b3:ec:f9:3f
+	lease=0x6523b000
+}
+{
+	ip_address=192.168.64.5
+	hw_address=ff,f1:f5:dd:7f:0:1:0:1:2c:3f:1c:b
+	lease=0x6523b000
+}
+",
+        )
+        .unwrap();
+        let ip_addrs = read(&path).unwrap();
+        assert_eq!(2, ip_addrs.len());
+        assert_eq!("192.168.64.8", ip_addrs["fa:5d:0b:89:61:16"]);
+        assert_eq!("192.168.64.4", ip_addrs["f6:db:b3:ec:f9:3f"]);
+        fs::remove_file(&path).unwrap();
+
+        assert!(read(&path).unwrap().is_empty(), "missing lease file");
+    }
+
+    #[test]
+    fn mac() {
+        assert_eq!("fa:5d:0b:89:61:16", parse_mac("fa:5d:b:89:61:16"));
+        assert_eq!("01:00:5e:00:00:fb", parse_mac("1:0:5e:0:0:fb"));
+    }
+}
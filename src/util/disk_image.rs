@@ -0,0 +1,88 @@
+use std::fmt;
+use std::fs::File;
+use std::os::unix::fs::FileExt as _;
+use std::path::Path;
+
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::util::qcow2;
+use crate::util::sparse_file;
+use crate::util::vmdk;
+
+pub enum ImageFormat {
+    Qcow2,
+    Vmdk,
+    Raw,
+}
+
+impl fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageFormat::Qcow2 => write!(f, "qcow2"),
+            ImageFormat::Vmdk => write!(f, "vmdk"),
+            ImageFormat::Raw => write!(f, "raw"),
+        }
+    }
+}
+
+// boot signature of MBR, also written by protective MBR of GPT disk
+const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+
+// detect by magic, raw disk must have partition table, return None if content is unknown
+pub fn detect(image: &File) -> Option<ImageFormat> {
+    let mut magic = [0; 4];
+    image.read_exact_at(&mut magic, 0).ok()?;
+    if magic == qcow2::MAGIC {
+        return Some(ImageFormat::Qcow2);
+    }
+    if magic == vmdk::MAGIC {
+        return Some(ImageFormat::Vmdk);
+    }
+    let mut boot_signature = [0; 2];
+    image.read_exact_at(&mut boot_signature, 510).ok()?;
+    (boot_signature == BOOT_SIGNATURE).then_some(ImageFormat::Raw)
+}
+
+pub fn virtual_size(image: &File, format: &ImageFormat) -> Result<u64> {
+    match format {
+        ImageFormat::Qcow2 => qcow2::virtual_size(image),
+        ImageFormat::Vmdk => vmdk::virtual_size(image),
+        ImageFormat::Raw => Ok(image.metadata().or_io_error("failed to read image metadata")?.len()),
+    }
+}
+
+// write image as sparse raw disk, target must not exist
+pub fn convert_to_raw(path: &Path, image: &File, format: &ImageFormat, to: &Path) -> Result<()> {
+    match format {
+        ImageFormat::Qcow2 => qcow2::to_raw(image, &File::create_new(to).or_io_error("failed to create file")?),
+        ImageFormat::Vmdk => vmdk::to_raw(image, &File::create_new(to).or_io_error("failed to create file")?),
+        ImageFormat::Raw => sparse_file::copy(path, to),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn detect_format() {
+        let path = env::temp_dir().join(Uuid::now_v7().to_string());
+        let mut content = vec![0; 1024];
+        fs::write(&path, &content).unwrap();
+        assert!(detect(&File::open(&path).unwrap()).is_none(), "unknown content");
+
+        content[510..512].copy_from_slice(&BOOT_SIGNATURE);
+        fs::write(&path, &content).unwrap();
+        assert!(matches!(detect(&File::open(&path).unwrap()), Some(ImageFormat::Raw)), "raw disk with partition table");
+
+        content[..4].copy_from_slice(qcow2::MAGIC);
+        fs::write(&path, &content).unwrap();
+        assert!(matches!(detect(&File::open(&path).unwrap()), Some(ImageFormat::Qcow2)), "qcow2 magic");
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,4 +1,11 @@
 use std::ffi::CString;
+use std::fs::File;
+use std::io;
+use std::io::Seek as _;
+use std::io::SeekFrom;
+use std::io::Write as _;
+use std::mem::ManuallyDrop;
+use std::os::fd::FromRawFd as _;
 use std::os::fd::RawFd;
 use std::path::Path;
 
@@ -12,7 +19,7 @@ impl FileLock {
     // fd returned by File.as_raw_fd() doesn't work with fcntl
     pub fn new(path: &Path) -> Self {
         let path = CString::new(path.to_string_lossy().as_bytes()).unwrap();
-        let fd = unsafe { libc::open(path.as_ptr(), libc::O_WRONLY) };
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_RDWR) };
         Self { fd }
     }
 
@@ -20,6 +27,15 @@ impl FileLock {
     // close fd will release all locks of current process !!! e.g. lock one file, then read the file / close file
     // https://apenwarr.ca/log/20101213
     pub fn lock(&self) -> bool {
+        self.set_write_lock(libc::F_SETLK)
+    }
+
+    // same as lock, but wait until lock is released by other process
+    pub fn lock_wait(&self) -> bool {
+        self.set_write_lock(libc::F_SETLKW)
+    }
+
+    fn set_write_lock(&self, command: libc::c_int) -> bool {
         let lock = libc::flock {
             l_start: 0,
             l_len: 0,
@@ -27,7 +43,7 @@ impl FileLock {
             l_type: libc::F_WRLCK,
             l_whence: libc::SEEK_SET as libc::c_short,
         };
-        let result = unsafe { libc::fcntl(self.fd, libc::F_SETLK, &lock) };
+        let result = unsafe { libc::fcntl(self.fd, command, &lock) };
         result == 0
     }
 
@@ -45,6 +61,25 @@ impl FileLock {
         }
         (lock.l_type == libc::F_WRLCK).then_some(lock.l_pid)
     }
+
+    // read / write locked file via lock fd, open and close the file again will release the lock
+    pub fn read(&self) -> io::Result<String> {
+        let mut file = self.file();
+        file.seek(SeekFrom::Start(0))?;
+        io::read_to_string(&*file)
+    }
+
+    pub fn write(&self, content: &[u8]) -> io::Result<()> {
+        let mut file = self.file();
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(content)
+    }
+
+    // fd is owned by lock, file must not close it
+    fn file(&self) -> ManuallyDrop<File> {
+        ManuallyDrop::new(unsafe { File::from_raw_fd(self.fd) })
+    }
 }
 
 impl Drop for FileLock {
@@ -55,3 +90,58 @@ impl Drop for FileLock {
         }
     }
 }
+
+// flock on dir, unlike fcntl lock, it's bound to open file description, so it's kept if other fd is closed,
+// and follows dir after rename
+pub struct DirLock {
+    fd: RawFd,
+}
+
+impl DirLock {
+    // return None if dir is locked by others or can't be opened
+    pub fn lock(path: &Path) -> Option<Self> {
+        let path = CString::new(path.to_string_lossy().as_bytes()).ok()?;
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY) };
+        if fd < 0 {
+            return None;
+        }
+        let lock = DirLock { fd };
+        let result = unsafe { libc::flock(fd, libc::LOCK_EX | libc::LOCK_NB) };
+        (result == 0).then_some(lock)
+    }
+
+    pub fn locked(path: &Path) -> bool {
+        path.is_dir() && DirLock::lock(path).is_none()
+    }
+}
+
+impl Drop for DirLock {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn dir_lock() {
+        let dir = env::temp_dir().join(Uuid::now_v7().to_string());
+        fs::create_dir_all(&dir).unwrap();
+        assert!(!DirLock::locked(&dir), "dir is not locked");
+        let lock = DirLock::lock(&dir).unwrap();
+        assert!(DirLock::locked(&dir), "dir is locked");
+        assert!(DirLock::lock(&dir).is_none(), "lock is exclusive");
+        drop(lock);
+        assert!(!DirLock::locked(&dir), "lock is released");
+        fs::remove_dir(&dir).unwrap();
+    }
+}
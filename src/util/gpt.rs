@@ -0,0 +1,382 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::os::unix::fs::FileExt as _;
+
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::bytes::le_bytes;
+use crate::util::bytes::le_u32;
+use crate::util::bytes::le_u64;
+use crate::util::bytes::put;
+
+// virtualization framework uses 512 bytes sector for disk image
+pub const SECTOR_SIZE: u64 = 512;
+const SIGNATURE: &[u8] = b"EFI PART";
+const HEADER_SIZE: usize = 92;
+const PROTECTIVE_MBR_TYPE: u8 = 0xee;
+
+pub struct Gpt {
+    pub header: Header,
+    pub partitions: Vec<Partition>,
+    // raw partition entry array, unused entries and unknown fields are kept as is
+    entries: Vec<u8>,
+}
+
+pub struct Header {
+    pub revision: u32,
+    pub current_lba: u64,
+    pub backup_lba: u64,
+    pub first_usable_lba: u64,
+    pub last_usable_lba: u64,
+    pub disk_guid: [u8; 16],
+    pub partition_entry_lba: u64,
+    pub partition_entry_count: u32,
+    pub partition_entry_size: u32,
+}
+
+pub struct Partition {
+    // index in partition entry array
+    pub index: usize,
+    pub type_guid: [u8; 16],
+    pub first_lba: u64,
+    pub last_lba: u64,
+    pub name: String,
+}
+
+impl Gpt {
+    // last lba used by partitions
+    pub fn last_used_lba(&self) -> Option<u64> {
+        self.partitions.iter().map(|partition| partition.last_lba).max()
+    }
+
+    // sectors of partition entry array
+    pub fn entry_sectors(&self) -> u64 {
+        (u64::from(self.header.partition_entry_count) * u64::from(self.header.partition_entry_size))
+            .div_ceil(SECTOR_SIZE)
+    }
+
+    // backup partition entries and header take the end of disk
+    pub fn min_disk_size(&self) -> u64 {
+        (self.last_used_lba().unwrap_or(self.header.first_usable_lba) + 1 + self.entry_sectors() + 1) * SECTOR_SIZE
+    }
+}
+
+impl Partition {
+    pub fn size(&self) -> u64 {
+        (self.last_lba + 1).saturating_sub(self.first_lba) * SECTOR_SIZE
+    }
+}
+
+// read primary gpt, return None if disk is not partitioned yet
+pub fn read(file: &File) -> Result<Option<Gpt>> {
+    let mut block = [0; SECTOR_SIZE as usize];
+    if file.metadata().or_io_error("failed to read disk metadata")?.len() < SECTOR_SIZE * 2 {
+        return Ok(None);
+    }
+    file.read_exact_at(&mut block, SECTOR_SIZE).or_io_error("failed to read gpt header")?;
+    if block.get(..8) != Some(SIGNATURE) {
+        return Ok(None);
+    }
+
+    let header_size = le_u32(&block, 12);
+    let header_bytes = block.get(..header_size as usize).ok_or_else(|| invalid_gpt("invalid header size"))?;
+    let mut crc_bytes = header_bytes.to_vec();
+    put(&mut crc_bytes, 16, &[0; 4]);
+    if crc32(&crc_bytes) != le_u32(&block, 16) {
+        return Err(invalid_gpt("header checksum mismatch"));
+    }
+
+    let header = Header {
+        revision: le_u32(&block, 8),
+        current_lba: le_u64(&block, 24),
+        backup_lba: le_u64(&block, 32),
+        first_usable_lba: le_u64(&block, 40),
+        last_usable_lba: le_u64(&block, 48),
+        disk_guid: guid(&block, 56),
+        partition_entry_lba: le_u64(&block, 72),
+        partition_entry_count: le_u32(&block, 80),
+        partition_entry_size: le_u32(&block, 84),
+    };
+    if header.partition_entry_size < 128 || header.partition_entry_count > 1024 {
+        return Err(invalid_gpt("invalid partition entry size or count"));
+    }
+
+    let mut entries = vec![0; header.partition_entry_count as usize * header.partition_entry_size as usize];
+    file.read_exact_at(&mut entries, header.partition_entry_lba * SECTOR_SIZE)
+        .or_io_error("failed to read gpt partition entries")?;
+    if crc32(&entries) != le_u32(&block, 88) {
+        return Err(invalid_gpt("partition entries checksum mismatch"));
+    }
+    let partitions = entries
+        .chunks_exact(header.partition_entry_size as usize)
+        .enumerate()
+        .map(|(index, entry)| partition(index, entry))
+        .filter(|partition| partition.type_guid != [0; 16])
+        .collect();
+    Ok(Some(Gpt { header, partitions, entries }))
+}
+
+// move backup header and entries to end of disk of disk_size, e.g. after disk.img is grown, or before it's truncated,
+// so disk always has valid backup, and grow last partition to fill disk if requested
+pub fn relocate_backup(file: &File, gpt: &mut Gpt, disk_size: u64, grow_last_partition: bool) -> Result<()> {
+    if disk_size < gpt.min_disk_size() {
+        return Err(invalid_gpt("disk is smaller than partitions"));
+    }
+    let old_backup_lba = gpt.header.backup_lba;
+    let backup_lba = disk_size / SECTOR_SIZE - 1;
+    let backup_entry_lba = backup_lba - gpt.entry_sectors();
+    gpt.header.backup_lba = backup_lba;
+    gpt.header.last_usable_lba = backup_entry_lba - 1;
+
+    if grow_last_partition {
+        let last_usable_lba = gpt.header.last_usable_lba;
+        let entry_size = gpt.header.partition_entry_size as usize;
+        if let Some(partition) = gpt.partitions.iter_mut().max_by_key(|partition| partition.last_lba) {
+            partition.last_lba = last_usable_lba;
+            put(&mut gpt.entries, partition.index * entry_size + 40, &le_bytes(last_usable_lba, 8));
+        }
+    }
+
+    // old backup header is in middle of disk after grow, clear it to avoid confusion
+    if old_backup_lba > 1 && old_backup_lba < backup_lba {
+        file.write_all_at(&[0; SECTOR_SIZE as usize], old_backup_lba * SECTOR_SIZE)
+            .or_io_error("failed to clear old gpt backup header")?;
+    }
+    file.write_all_at(&gpt.entries, gpt.header.partition_entry_lba * SECTOR_SIZE)
+        .or_io_error("failed to write gpt partition entries")?;
+    file.write_all_at(&gpt.entries, backup_entry_lba * SECTOR_SIZE)
+        .or_io_error("failed to write gpt backup partition entries")?;
+    let primary = header_block(&gpt.header, 1, backup_lba, gpt.header.partition_entry_lba, &gpt.entries);
+    file.write_all_at(&primary, SECTOR_SIZE).or_io_error("failed to write gpt header")?;
+    let backup = header_block(&gpt.header, backup_lba, 1, backup_entry_lba, &gpt.entries);
+    file.write_all_at(&backup, backup_lba * SECTOR_SIZE).or_io_error("failed to write gpt backup header")?;
+    gpt.header.current_lba = 1;
+    update_protective_mbr(file, disk_size / SECTOR_SIZE)?;
+    file.sync_all().or_io_error("failed to sync disk")
+}
+
+fn header_block(header: &Header, current_lba: u64, backup_lba: u64, entry_lba: u64, entries: &[u8]) -> Vec<u8> {
+    let mut block = vec![0; SECTOR_SIZE as usize];
+    put(&mut block, 0, SIGNATURE);
+    put(&mut block, 8, &le_bytes(u64::from(header.revision), 4));
+    put(&mut block, 12, &le_bytes(HEADER_SIZE as u64, 4));
+    put(&mut block, 24, &le_bytes(current_lba, 8));
+    put(&mut block, 32, &le_bytes(backup_lba, 8));
+    put(&mut block, 40, &le_bytes(header.first_usable_lba, 8));
+    put(&mut block, 48, &le_bytes(header.last_usable_lba, 8));
+    put(&mut block, 56, &header.disk_guid);
+    put(&mut block, 72, &le_bytes(entry_lba, 8));
+    put(&mut block, 80, &le_bytes(u64::from(header.partition_entry_count), 4));
+    put(&mut block, 84, &le_bytes(u64::from(header.partition_entry_size), 4));
+    put(&mut block, 88, &le_bytes(u64::from(crc32(entries)), 4));
+    let crc = crc32(block.get(..HEADER_SIZE).unwrap_or_default());
+    put(&mut block, 16, &le_bytes(u64::from(crc), 4));
+    block
+}
+
+// protective mbr covers whole disk, or max value if disk is larger than 2TiB
+fn update_protective_mbr(file: &File, sectors: u64) -> Result<()> {
+    let mut mbr = [0; SECTOR_SIZE as usize];
+    file.read_exact_at(&mut mbr, 0).or_io_error("failed to read mbr")?;
+    if mbr.get(450) != Some(&PROTECTIVE_MBR_TYPE) {
+        return Ok(());
+    }
+    let size = (sectors - 1).min(u64::from(u32::MAX));
+    file.write_all_at(&le_bytes(size, 4), 446 + 12).or_io_error("failed to write mbr")
+}
+
+fn partition(index: usize, entry: &[u8]) -> Partition {
+    // name is utf-16le, nul terminated
+    let name: Vec<u16> = entry
+        .get(56..128)
+        .unwrap_or_default()
+        .chunks_exact(2)
+        .map(|bytes| bytes.iter().rev().fold(0, |value, byte| (value << 8) | u16::from(*byte)))
+        .take_while(|char| *char != 0)
+        .collect();
+    Partition {
+        index,
+        type_guid: guid(entry, 0),
+        first_lba: le_u64(entry, 32),
+        last_lba: le_u64(entry, 40),
+        name: String::from_utf16_lossy(&name),
+    }
+}
+
+// first three fields are little endian, e.g. 0FC63DAF-8483-4772-8E79-3D69D8477DE4
+pub fn format_guid(guid: &[u8; 16]) -> String {
+    let mut text = String::with_capacity(36);
+    for (index, position) in [3, 2, 1, 0, 5, 4, 7, 6, 8, 9, 10, 11, 12, 13, 14, 15].into_iter().enumerate() {
+        if matches!(index, 4 | 6 | 8 | 10) {
+            text.push('-');
+        }
+        let byte = guid.get(position).copied().unwrap_or_default();
+        write!(text, "{byte:02X}").unwrap_or_else(|err| panic!("failed to write string, err={err}"));
+    }
+    text
+}
+
+pub fn partition_type(type_guid: &[u8; 16]) -> &'static str {
+    match format_guid(type_guid).as_str() {
+        "C12A7328-F81F-11D2-BA4B-00A0C93EC93B" => "EFI system",
+        "0FC63DAF-8483-4772-8E79-3D69D8477DE4" => "Linux filesystem",
+        "0657FD6D-A4AB-43C4-84E5-0933C84B4F4F" => "Linux swap",
+        "E6D6D379-F507-44C2-A23C-238F2A3DF928" => "Linux LVM",
+        "B921B045-1DF0-41C3-AF44-4C6F280D3FAE" => "Linux root (ARM-64)",
+        "7C3457EF-0000-11AA-AA11-00306543ECAC" => "Apple APFS",
+        "69646961-6700-11AA-AA11-00306543ECAC" => "Apple boot",
+        "52637672-7900-11AA-AA11-00306543ECAC" => "Apple recovery",
+        _ => "unknown",
+    }
+}
+
+fn guid(bytes: &[u8], offset: usize) -> [u8; 16] {
+    let mut guid = [0; 16];
+    for (target, byte) in guid.iter_mut().zip(bytes.iter().skip(offset)) {
+        *target = *byte;
+    }
+    guid
+}
+
+// crc32 (ieee 802.3), as used by gpt
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 0 { crc >> 1 } else { (crc >> 1) ^ 0xedb8_8320 };
+        }
+    }
+    !crc
+}
+
+fn invalid_gpt(message: &str) -> VzError {
+    VzError::InvalidArgument(format!("invalid gpt, {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    const LINUX_FILESYSTEM: [u8; 16] =
+        [0xaf, 0x3d, 0xc6, 0x0f, 0x83, 0x84, 0x72, 0x47, 0x8e, 0x79, 0x3d, 0x69, 0xd8, 0x47, 0x7d, 0xe4];
+
+    // 1MiB disk with one partition, lba 34-1500
+    fn create_disk() -> (PathBuf, File) {
+        let path = env::temp_dir().join(Uuid::now_v7().to_string());
+        let file = File::options().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+        file.set_len(1024 * 1024).unwrap();
+
+        let mut mbr = vec![0; 512];
+        put(&mut mbr, 450, &[PROTECTIVE_MBR_TYPE]);
+        put(&mut mbr, 454, &le_bytes(1, 4));
+        put(&mut mbr, 458, &le_bytes(2047, 4));
+        file.write_all_at(&mbr, 0).unwrap();
+
+        let mut entries = vec![0; 128 * 128];
+        put(&mut entries, 0, &LINUX_FILESYSTEM);
+        put(&mut entries, 16, &[0x11; 16]);
+        put(&mut entries, 32, &le_bytes(34, 8));
+        put(&mut entries, 40, &le_bytes(1500, 8));
+        put(&mut entries, 56, &[b'r', 0, b'o', 0, b'o', 0, b't', 0]);
+        let header = Header {
+            revision: 0x0001_0000,
+            current_lba: 1,
+            backup_lba: 2047,
+            first_usable_lba: 34,
+            last_usable_lba: 2014,
+            disk_guid: [0x22; 16],
+            partition_entry_lba: 2,
+            partition_entry_count: 128,
+            partition_entry_size: 128,
+        };
+        file.write_all_at(&header_block(&header, 1, 2047, 2, &entries), SECTOR_SIZE).unwrap();
+        file.write_all_at(&entries, 2 * SECTOR_SIZE).unwrap();
+        (path, file)
+    }
+
+    #[test]
+    fn checksum() {
+        assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+        assert_eq!(0, crc32(b""));
+    }
+
+    #[test]
+    fn read_gpt() {
+        let (path, file) = create_disk();
+        let gpt = read(&file).unwrap().unwrap();
+        assert_eq!(1, gpt.partitions.len());
+        let partition = &gpt.partitions[0];
+        assert_eq!("root", partition.name);
+        assert_eq!("Linux filesystem", partition_type(&partition.type_guid));
+        assert_eq!("0FC63DAF-8483-4772-8E79-3D69D8477DE4", format_guid(&partition.type_guid));
+        assert_eq!((1500 - 34 + 1) * 512, partition.size());
+        assert_eq!((1501 + 32 + 1) * 512, gpt.min_disk_size());
+
+        file.write_all_at(&[1], 2 * SECTOR_SIZE + 100).unwrap();
+        assert!(read(&file).is_err(), "checksum mismatch");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_unpartitioned_disk() {
+        let path = env::temp_dir().join(Uuid::now_v7().to_string());
+        let file = File::options().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+        file.set_len(1024 * 1024).unwrap();
+        assert!(read(&file).unwrap().is_none(), "disk is not partitioned");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn relocate_backup_after_grow() {
+        let (path, file) = create_disk();
+        file.set_len(2 * 1024 * 1024).unwrap();
+        let mut gpt = read(&file).unwrap().unwrap();
+        relocate_backup(&file, &mut gpt, 2 * 1024 * 1024, true).unwrap();
+
+        let relocated = read(&file).unwrap().unwrap();
+        assert_eq!(4095, relocated.header.backup_lba);
+        assert_eq!(4095 - 32 - 1, relocated.header.last_usable_lba);
+        assert_eq!(relocated.header.last_usable_lba, relocated.partitions[0].last_lba);
+
+        let mut backup = [0; 512];
+        file.read_exact_at(&mut backup, 4095 * SECTOR_SIZE).unwrap();
+        assert_eq!(SIGNATURE, &backup[..8]);
+        assert_eq!(4095, le_u64(&backup, 24));
+        assert_eq!(4095 - 32, le_u64(&backup, 72));
+        let mut old_backup = [0; 512];
+        file.read_exact_at(&mut old_backup, 2047 * SECTOR_SIZE).unwrap();
+        assert_eq!([0; 512], old_backup);
+        let mut mbr = [0; 512];
+        file.read_exact_at(&mut mbr, 0).unwrap();
+        assert_eq!(4095, le_u32(&mbr, 458));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn relocate_backup_before_shrink() {
+        let (path, file) = create_disk();
+        let mut gpt = read(&file).unwrap().unwrap();
+        relocate_backup(&file, &mut gpt, 1600 * 512, false).unwrap();
+        assert_eq!(2048 * 512, file.metadata().unwrap().len(), "file is not truncated");
+        let mut backup = [0; 512];
+        file.read_exact_at(&mut backup, 1599 * SECTOR_SIZE).unwrap();
+        assert_eq!(SIGNATURE, &backup[..8]);
+
+        file.set_len(1600 * 512).unwrap();
+        let relocated = read(&file).unwrap().unwrap();
+        assert_eq!(1599, relocated.header.backup_lba);
+        assert_eq!(1500, relocated.partitions[0].last_lba);
+
+        let mut truncated = read(&file).unwrap().unwrap();
+        assert!(relocate_backup(&file, &mut truncated, 1510 * 512, false).is_err(), "disk is smaller than partitions");
+        fs::remove_file(&path).unwrap();
+    }
+}
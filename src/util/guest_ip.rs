@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use tracing::warn;
+
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::dhcpd_leases;
+
+// mac => ip of guests, dhcpd leases first, arp only knows guests recently sent traffic
+pub fn ip_addrs(dhcpd_leases: &Path) -> Result<HashMap<String, String>> {
+    let mut ip_addrs = dhcpd_leases::read(dhcpd_leases)?;
+    match arp_ip_addrs() {
+        Ok(arp_ip_addrs) => {
+            for (mac, ip) in arp_ip_addrs {
+                ip_addrs.entry(mac).or_insert(ip);
+            }
+        }
+        Err(err) => warn!("failed to read arp table, err={err}"),
+    }
+    Ok(ip_addrs)
+}
+
+fn arp_ip_addrs() -> Result<HashMap<String, String>> {
+    let output = Command::new("arp").arg("-anl").output().or_io_error("failed to execute arp")?;
+    if !output.status.success() {
+        return Err(VzError::Io(
+            "failed to execute arp".to_owned(),
+            io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_owned()),
+        ));
+    }
+    let output = String::from_utf8_lossy(&output.stdout);
+    Ok(parse_arp_output(&output))
+}
+
+fn parse_arp_output(output: &str) -> HashMap<String, String> {
+    let mut ip_addrs = HashMap::new();
+    for line in output.lines().skip(1) {
+        let mut parts = line.split_whitespace();
+        if let (Some(ip), Some(mac_str)) = (parts.next(), parts.next()) {
+            let mac = dhcpd_leases::parse_mac(mac_str);
+            ip_addrs.insert(mac, ip.to_owned());
+        }
+    }
+    ip_addrs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_arp_output;
+
+    #[test]
+    fn parse_arp_output_example() {
+        let ip_addrs = parse_arp_output(
+            "Neighbor                Linklayer Address Expire(O) Expire(I)          Netif Refs Prbs
+            10.11.101.76            f0:18:98:3c:4a:cc expired   expired        en0    1
+            192.168.64.3            f6:db:b3:ec:f9:3f 2m42s     2m34s     bridge10    1
+            192.168.64.8            fa:5d:b:89:61:16  2m33s     1m21s     bridge10    1
+            224.0.0.251             1:0:5e:0:0:fb     (none)    (none)         en0",
+        );
+        assert_eq!("192.168.64.3", ip_addrs["f6:db:b3:ec:f9:3f"]);
+        assert_eq!("192.168.64.8", ip_addrs["fa:5d:0b:89:61:16"]);
+    }
+}
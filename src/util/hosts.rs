@@ -0,0 +1,147 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::file_lock::FileLock;
+
+const BEGIN_MARKER: &str = "# BEGIN vz managed hosts, do not edit";
+const END_MARKER: &str = "# END vz managed hosts";
+
+// fcntl lock doesn't exclude threads of same process
+static UPDATE_LOCK: Mutex<()> = Mutex::new(());
+
+// replace managed block with (ip, hostname) entries, lines outside markers are kept as is, return true if file is changed
+pub fn update(path: &Path, entries: &[(String, String)]) -> Result<bool> {
+    // rewrite target of symlink, e.g. /etc/hosts => /private/etc/hosts
+    let path = fs::canonicalize(path).unwrap_or_else(|_err| path.to_path_buf());
+    let file_name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+
+    // hold lock until renamed, otherwise concurrent updates may drop entries of each other,
+    // lock separate file, as hosts file is replaced by rename
+    let _guard = UPDATE_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let lock_path = path.with_file_name(format!(".{file_name}.lock"));
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&lock_path)
+        .or_io_error("failed to create hosts lock file")?;
+    let lock = FileLock::new(&lock_path);
+    if !lock.lock_wait() {
+        return Err(VzError::Io("failed to lock hosts file".to_owned(), io::Error::last_os_error()));
+    }
+
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err).or_io_error("failed to read hosts file"),
+    };
+    let updated = replace_block(&content, entries)?;
+    if updated == content {
+        return Ok(false);
+    }
+
+    // write temp file in same dir then rename, so reader never sees partial file
+    let temp_path = path.with_file_name(format!(".{file_name}.{}", Uuid::now_v7()));
+    let result = fs::write(&temp_path, &updated)
+        .and_then(|()| match fs::metadata(&path) {
+            Ok(metadata) => fs::set_permissions(&temp_path, metadata.permissions()),
+            Err(_) => Ok(()),
+        })
+        .and_then(|()| fs::rename(&temp_path, &path));
+    if result.is_err()
+        && let Err(err) = fs::remove_file(&temp_path)
+    {
+        warn!("failed to remove temp hosts file, path={}, err={err}", temp_path.to_string_lossy());
+    }
+    result.or_io_error("failed to write hosts file")?;
+    Ok(true)
+}
+
+fn replace_block(content: &str, entries: &[(String, String)]) -> Result<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let begin = lines.iter().position(|line| line.trim() == BEGIN_MARKER);
+    let end = lines.iter().position(|line| line.trim() == END_MARKER);
+    let (before, after) = match (begin, end) {
+        (Some(begin), Some(end)) if begin < end => {
+            (lines.get(..begin).unwrap_or_default(), lines.get(end + 1..).unwrap_or_default())
+        }
+        (None, None) if entries.is_empty() => return Ok(content.to_owned()),
+        (None, None) => (lines.as_slice(), [].as_slice()),
+        _ => {
+            return Err(VzError::InvalidArgument(format!(
+                "hosts file has incomplete vz managed block, fix '{BEGIN_MARKER}' and '{END_MARKER}' lines manually"
+            )));
+        }
+    };
+    let mut updated: Vec<String> = before.iter().map(|line| (*line).to_owned()).collect();
+    if !entries.is_empty() {
+        updated.push(BEGIN_MARKER.to_owned());
+        updated.extend(entries.iter().map(|(ip, hostname)| format!("{ip}\t{hostname}")));
+        updated.push(END_MARKER.to_owned());
+    }
+    updated.extend(after.iter().map(|line| (*line).to_owned()));
+    if updated.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(format!("{}\n", updated.join("\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn entries(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries.iter().map(|(ip, hostname)| ((*ip).to_owned(), (*hostname).to_owned())).collect()
+    }
+
+    #[test]
+    fn replace() {
+        let content = "127.0.0.1\tlocalhost\n";
+        let added = replace_block(content, &entries(&[("192.168.64.3", "debian.vz")])).unwrap();
+        assert_eq!(
+            "127.0.0.1\tlocalhost\n# BEGIN vz managed hosts, do not edit\n192.168.64.3\tdebian.vz\n# END vz managed hosts\n",
+            added
+        );
+
+        let edited = format!("{added}10.0.0.1\tnas\n");
+        let replaced = replace_block(&edited, &entries(&[("192.168.64.4", "debian.vz")])).unwrap();
+        assert_eq!(
+            "127.0.0.1\tlocalhost\n# BEGIN vz managed hosts, do not edit\n192.168.64.4\tdebian.vz\n# END vz managed hosts\n10.0.0.1\tnas\n",
+            replaced
+        );
+
+        assert_eq!("127.0.0.1\tlocalhost\n10.0.0.1\tnas\n", replace_block(&replaced, &[]).unwrap());
+        assert_eq!("no newline", replace_block("no newline", &[]).unwrap());
+    }
+
+    #[test]
+    fn incomplete_block() {
+        let content = "127.0.0.1\tlocalhost\n# BEGIN vz managed hosts, do not edit\n192.168.64.3\tdebian.vz\n";
+        assert!(replace_block(content, &[]).is_err(), "user content after begin marker must not be removed");
+    }
+
+    #[test]
+    fn update_file() {
+        let path = env::temp_dir().join(Uuid::now_v7().to_string());
+        fs::write(&path, "127.0.0.1\tlocalhost\n").unwrap();
+        let entries = entries(&[("192.168.64.3", "debian.vz")]);
+        assert!(update(&path, &entries).unwrap(), "block is added");
+        assert!(!update(&path, &entries).unwrap(), "same entries");
+        assert!(fs::read_to_string(&path).unwrap().contains("192.168.64.3\tdebian.vz\n"), "entry is written");
+        assert!(update(&path, &[]).unwrap(), "block is removed");
+        assert_eq!("127.0.0.1\tlocalhost\n", fs::read_to_string(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(env::temp_dir().join(format!(".{}.lock", path.file_name().unwrap().to_string_lossy())))
+            .unwrap();
+    }
+}
@@ -0,0 +1,264 @@
+use crate::error::Result;
+use crate::error::VzError;
+
+// RFC 1951, used to decompress qcow2 compressed clusters and vmdk grains
+const LENGTH_BASE: [u16; 29] =
+    [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+#[allow(clippy::decimal_literal_representation)]
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] =
+    [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+// decompress raw deflate stream, output must not exceed limit
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut reader = BitReader { data, position: 0, buffer: 0, count: 0 };
+    let mut output = Vec::with_capacity(limit);
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => stored_block(&mut reader, &mut output, limit)?,
+            1 => {
+                let (literal, distance) = fixed_huffman();
+                huffman_block(&mut reader, &mut output, limit, &literal, &distance)?;
+            }
+            2 => {
+                let (literal, distance) = dynamic_huffman(&mut reader)?;
+                huffman_block(&mut reader, &mut output, limit, &literal, &distance)?;
+            }
+            _ => return Err(invalid_data("invalid block type")),
+        }
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitReader<'_> {
+    // bits are packed starting from least significant bit
+    fn bits(&mut self, count: u32) -> Result<u32> {
+        while self.count < count {
+            let byte = self.data.get(self.position).ok_or_else(|| invalid_data("unexpected end of data"))?;
+            self.buffer |= u32::from(*byte) << self.count;
+            self.position += 1;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << count) - 1);
+        self.buffer >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+}
+
+struct Huffman {
+    // number of codes of each length
+    counts: [u16; 16],
+    // symbols ordered by code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; 16];
+        for length in lengths {
+            if let Some(count) = counts.get_mut(usize::from(*length)) {
+                *count += 1;
+            }
+        }
+        let mut symbols = vec![];
+        for length in 1..16 {
+            for (symbol, _) in (0..).zip(lengths).filter(|(_, value)| **value == length) {
+                symbols.push(symbol);
+            }
+        }
+        if let Some(count) = counts.first_mut() {
+            *count = 0;
+        }
+        Huffman { counts, symbols }
+    }
+
+    // canonical huffman code, codes of same length are consecutive
+    fn decode(&self, reader: &mut BitReader) -> Result<u16> {
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for count in self.counts.iter().skip(1) {
+            code |= reader.bits(1)?;
+            let count = u32::from(*count);
+            if code < first + count {
+                let symbol = self.symbols.get((index + code - first) as usize);
+                return symbol.copied().ok_or_else(|| invalid_data("invalid huffman code"));
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid_data("invalid huffman code"))
+    }
+}
+
+fn stored_block(reader: &mut BitReader, output: &mut Vec<u8>, limit: usize) -> Result<()> {
+    // stored block starts at byte boundary
+    reader.buffer = 0;
+    reader.count = 0;
+    let length = reader.bits(16)?;
+    let complement = reader.bits(16)?;
+    if length != !complement & 0xffff {
+        return Err(invalid_data("invalid stored block length"));
+    }
+    let data = reader
+        .data
+        .get(reader.position..reader.position + length as usize)
+        .ok_or_else(|| invalid_data("unexpected end of data"))?;
+    if output.len() + data.len() > limit {
+        return Err(invalid_data("output exceeds limit"));
+    }
+    output.extend_from_slice(data);
+    reader.position += length as usize;
+    Ok(())
+}
+
+fn huffman_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    limit: usize,
+    literal: &Huffman,
+    distance: &Huffman,
+) -> Result<()> {
+    loop {
+        let symbol = literal.decode(reader)?;
+        if symbol == 256 {
+            return Ok(());
+        }
+        if output.len() >= limit {
+            return Err(invalid_data("output exceeds limit"));
+        }
+        if let Ok(byte) = u8::try_from(symbol) {
+            output.push(byte);
+            continue;
+        }
+
+        let length_code = usize::from(symbol - 257);
+        let (Some(length_base), Some(length_extra)) = (LENGTH_BASE.get(length_code), LENGTH_EXTRA.get(length_code))
+        else {
+            return Err(invalid_data("invalid length code"));
+        };
+        let length = usize::from(*length_base) + reader.bits(u32::from(*length_extra))? as usize;
+        let distance_code = usize::from(distance.decode(reader)?);
+        let (Some(distance_base), Some(distance_extra)) =
+            (DISTANCE_BASE.get(distance_code), DISTANCE_EXTRA.get(distance_code))
+        else {
+            return Err(invalid_data("invalid distance code"));
+        };
+        let back = usize::from(*distance_base) + reader.bits(u32::from(*distance_extra))? as usize;
+        if back > output.len() {
+            return Err(invalid_data("distance too far back"));
+        }
+        if output.len() + length > limit {
+            return Err(invalid_data("output exceeds limit"));
+        }
+        // copied range may overlap with output being written
+        for _ in 0..length {
+            let byte = output.get(output.len() - back).copied().unwrap_or_default();
+            output.push(byte);
+        }
+    }
+}
+
+fn fixed_huffman() -> (Huffman, Huffman) {
+    let mut lengths = [8; 288];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..144 | 280.. => 8,
+            144..256 => 9,
+            _ => 7,
+        };
+    }
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_huffman(reader: &mut BitReader) -> Result<(Huffman, Huffman)> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0; 19];
+    for index in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        if let Some(length) = code_lengths.get_mut(*index) {
+            *length = reader.bits(3)? as u8;
+        }
+    }
+    let code_length = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let symbol = code_length.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..16 => (symbol as u8, 1),
+            16 => {
+                let previous = lengths.last().copied().ok_or_else(|| invalid_data("no previous code length"))?;
+                (previous, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(value);
+        }
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(invalid_data("too many code lengths"));
+    }
+    let (literal, distance) = lengths.split_at(literal_count);
+    Ok((Huffman::new(literal), Huffman::new(distance)))
+}
+
+fn invalid_data(message: &str) -> VzError {
+    VzError::InvalidArgument(format!("invalid compressed data, {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inflate_stored_block() {
+        let data = [0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c'];
+        assert_eq!(b"abc".to_vec(), inflate(&data, 3).unwrap());
+        assert!(inflate(&data, 2).is_err(), "output exceeds limit");
+    }
+
+    #[test]
+    fn inflate_fixed_huffman() {
+        // zlib.compressobj(9, zlib.DEFLATED, -15).compress(b"hello hello hello hello")
+        let data = [0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x27, 0x01];
+        assert_eq!(b"hello hello hello hello".to_vec(), inflate(&data, 1024).unwrap());
+    }
+
+    #[test]
+    fn inflate_dynamic_huffman() {
+        // raw deflate of 20 times "the quick brown fox jumps over the lazy dog {index}\n"
+        let data = [
+            0x95, 0xd1, 0xcb, 0x01, 0x82, 0x30, 0x14, 0x05, 0xd1, 0x3d, 0x55, 0xbc, 0x12, 0xbc, 0x22, 0x6a, 0xca, 0xe1,
+            0x13, 0x05, 0x3f, 0x04, 0x43, 0x22, 0x60, 0xf5, 0xd2, 0xc2, 0xac, 0x67, 0x76, 0x27, 0xf5, 0xde, 0x3e, 0x79,
+            0x68, 0x9f, 0xd6, 0xc4, 0xb0, 0x8c, 0x76, 0x0b, 0xab, 0x3d, 0xf2, 0x7b, 0x9a, 0x2d, 0x7c, 0x7d, 0xb4, 0xb4,
+            0xe7, 0x57, 0xfd, 0xdb, 0xac, 0x0b, 0x77, 0x3b, 0x14, 0x09, 0xdc, 0x42, 0xf7, 0x11, 0xdd, 0x25, 0xba, 0x4f,
+            0xe8, 0xae, 0xd0, 0x7d, 0x46, 0xf7, 0x05, 0xdd, 0x57, 0x74, 0x3b, 0xa6, 0x03, 0x31, 0x99, 0xa6, 0x18, 0xa7,
+            0x98, 0xa7, 0x18, 0xa8, 0x98, 0xa8, 0x18, 0xa9, 0x98, 0xa9, 0x18, 0xaa, 0x5c, 0xf1, 0x07,
+        ];
+        let expected: Vec<String> =
+            (0..20).map(|index| format!("the quick brown fox jumps over the lazy dog {index}\n")).collect();
+        assert_eq!(expected.concat().into_bytes(), inflate(&data, 4096).unwrap());
+    }
+}
@@ -0,0 +1,710 @@
+use std::collections::HashSet;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Read as _;
+use std::io::Seek as _;
+use std::io::SeekFrom;
+use std::os::unix::fs::FileExt as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+use tracing::warn;
+
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::bytes::be_bytes;
+use crate::util::bytes::le_bytes;
+use crate::util::bytes::le_u32;
+use crate::util::bytes::put;
+use crate::util::time;
+
+// ECMA-119 with joliet supplementary volume descriptor for long unicode names
+const SECTOR_SIZE: u64 = 2048;
+// system area takes sector 0-15
+const PRIMARY_VOLUME_DESCRIPTOR_SECTOR: u64 = 16;
+const JOLIET_VOLUME_DESCRIPTOR_SECTOR: u64 = 17;
+const TERMINATOR_SECTOR: u64 = 18;
+const PATH_TABLE_SECTOR: u64 = 19;
+const FLAG_DIRECTORY: u8 = 2;
+// UCS-2 level 3
+const JOLIET_ESCAPE_SEQUENCE: &[u8] = b"%/E";
+// file is single extent, size is u32
+const MAX_FILE_SIZE: u64 = u32::MAX as u64;
+// joliet identifier is up to 64 characters, ;1 version takes 2
+const JOLIET_NAME_LENGTH: usize = 62;
+const PRIMARY_NAME_LENGTH: usize = 30;
+
+pub enum IsoNode {
+    File { name: String, size: u64, source: Source },
+    Dir { name: String, children: Vec<IsoNode> },
+}
+
+pub enum Source {
+    Data(Vec<u8>),
+    Path(PathBuf),
+}
+
+impl IsoNode {
+    pub fn file(name: &str, data: Vec<u8>) -> Self {
+        IsoNode::File { name: name.to_owned(), size: data.len() as u64, source: Source::Data(data) }
+    }
+}
+
+// walk dir recursively, symlinks to files are followed, symlinks to dirs are skipped to avoid loops,
+// other special files are skipped
+pub fn scan_dir(dir: &Path) -> Result<Vec<IsoNode>> {
+    let mut nodes = vec![];
+    for entry in fs::read_dir(dir).or_io_error("failed to read dir")? {
+        let path = entry.or_io_error("failed to read dir")?.path();
+        let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let symlink = fs::symlink_metadata(&path).or_io_error("failed to read file metadata")?.is_symlink();
+        let metadata = fs::metadata(&path).or_io_error("failed to read file metadata")?;
+        if metadata.is_dir() && symlink {
+            warn!("skip symlink to dir, path={}", path.to_string_lossy());
+        } else if metadata.is_dir() {
+            nodes.push(IsoNode::Dir { name, children: scan_dir(&path)? });
+        } else if metadata.is_file() {
+            if metadata.len() > MAX_FILE_SIZE {
+                return Err(VzError::InvalidArgument(format!(
+                    "file is too large for iso, path={}",
+                    path.to_string_lossy()
+                )));
+            }
+            nodes.push(IsoNode::File { name, size: metadata.len(), source: Source::Path(path) });
+        }
+    }
+    Ok(nodes)
+}
+
+// volume label defaults to dir name
+pub fn volume_label(dir: &Path) -> String {
+    dir.file_name().map_or_else(|| "CDROM".to_owned(), |name| name.to_string_lossy().to_string())
+}
+
+// files are referred by index, file data is shared by primary and joliet hierarchy
+struct Tree<'a> {
+    dirs: Vec<(&'a str, Tree<'a>)>,
+    files: Vec<usize>,
+}
+
+struct FileExtent<'a> {
+    name: &'a str,
+    size: u64,
+    source: &'a Source,
+    sector: u64,
+}
+
+struct Dir<'a> {
+    tree: &'a Tree<'a>,
+    identifier: Vec<u8>,
+    // 1 based directory number of parent in path table
+    parent: usize,
+    children: Vec<usize>,
+    // file index and identifier
+    files: Vec<(usize, Vec<u8>)>,
+    sector: u64,
+    size: u64,
+}
+
+struct Record {
+    identifier: Vec<u8>,
+    sector: u64,
+    size: u64,
+    flags: u8,
+}
+
+struct VolumeDescriptor<'a> {
+    joliet: bool,
+    volume_id: &'a str,
+    sectors: u64,
+    root: &'a [u8],
+    path_table_size: u64,
+    l_path_table_sector: u64,
+    m_path_table_sector: u64,
+    recorded_at: &'a [u64; 6],
+}
+
+pub fn write(path: &Path, volume_id: &str, nodes: &[IsoNode]) -> Result<()> {
+    let recorded_at = time::utc(time::now());
+    let mut files = vec![];
+    let tree = build_tree(nodes, &mut files);
+    let mut primary = hierarchy(&tree, &files, false);
+    let mut joliet = hierarchy(&tree, &files, true);
+
+    // path tables of both hierarchies, directories, then file data
+    let path_table_sizes = [path_table_size(&primary), path_table_size(&joliet)];
+    let path_table_sectors = path_table_sizes.map(|size| size.div_ceil(SECTOR_SIZE));
+    let mut sector = PATH_TABLE_SECTOR + path_table_sectors.iter().sum::<u64>() * 2;
+    for dirs in [&mut primary, &mut joliet] {
+        for index in 0..dirs.len() {
+            // record sizes don't depend on location
+            let size = directory(&records(dirs, index, &files), &recorded_at).len() as u64;
+            if let Some(dir) = dirs.get_mut(index) {
+                dir.sector = sector;
+                dir.size = size;
+            }
+            sector += size / SECTOR_SIZE;
+        }
+    }
+    for file in &mut files {
+        file.sector = sector;
+        sector += file.size.div_ceil(SECTOR_SIZE);
+    }
+
+    let iso = File::create(path).or_io_error("failed to create iso")?;
+    iso.set_len(sector * SECTOR_SIZE).or_io_error("failed to resize iso")?;
+    let mut path_table_sector = PATH_TABLE_SECTOR;
+    for (dirs, is_joliet, path_table_size, table_sectors) in [
+        (&primary, false, path_table_sizes[0], path_table_sectors[0]),
+        (&joliet, true, path_table_sizes[1], path_table_sectors[1]),
+    ] {
+        let root = dirs.first().map(|dir| {
+            let record = Record { identifier: vec![0], sector: dir.sector, size: dir.size, flags: FLAG_DIRECTORY };
+            directory_record(&record, &recorded_at)
+        });
+        let descriptor = volume_descriptor(&VolumeDescriptor {
+            joliet: is_joliet,
+            volume_id,
+            sectors: sector,
+            root: &root.unwrap_or_default(),
+            path_table_size,
+            l_path_table_sector: path_table_sector,
+            m_path_table_sector: path_table_sector + table_sectors,
+            recorded_at: &recorded_at,
+        });
+        let descriptor_sector =
+            if is_joliet { JOLIET_VOLUME_DESCRIPTOR_SECTOR } else { PRIMARY_VOLUME_DESCRIPTOR_SECTOR };
+        iso.write_all_at(&descriptor, descriptor_sector * SECTOR_SIZE)
+            .or_io_error("failed to write volume descriptor")?;
+        iso.write_all_at(&path_table(dirs, false), path_table_sector * SECTOR_SIZE)
+            .or_io_error("failed to write path table")?;
+        iso.write_all_at(&path_table(dirs, true), (path_table_sector + table_sectors) * SECTOR_SIZE)
+            .or_io_error("failed to write path table")?;
+        path_table_sector += table_sectors * 2;
+        for (index, dir) in dirs.iter().enumerate() {
+            let directory = directory(&records(dirs, index, &files), &recorded_at);
+            iso.write_all_at(&directory, dir.sector * SECTOR_SIZE).or_io_error("failed to write directory")?;
+        }
+    }
+    let mut terminator = vec![0; SECTOR_SIZE as usize];
+    put(&mut terminator, 0, b"\xffCD001\x01");
+    iso.write_all_at(&terminator, TERMINATOR_SECTOR * SECTOR_SIZE)
+        .or_io_error("failed to write volume descriptor terminator")?;
+    for file in &files {
+        write_file(&iso, file)?;
+    }
+    iso.sync_all().or_io_error("failed to sync iso")
+}
+
+// read file in root dir of joliet hierarchy, e.g. iso written by write(), return None if file doesn't exist
+pub fn read_root_file(path: &Path, name: &str) -> Result<Option<Vec<u8>>> {
+    let iso = File::open(path).or_io_error("failed to open iso")?;
+    let mut descriptor = vec![0; SECTOR_SIZE as usize];
+    iso.read_exact_at(&mut descriptor, JOLIET_VOLUME_DESCRIPTOR_SECTOR * SECTOR_SIZE)
+        .or_io_error("failed to read volume descriptor")?;
+    if descriptor.get(1..6) != Some(b"CD001") || descriptor.get(88..91) != Some(JOLIET_ESCAPE_SEQUENCE) {
+        return Err(VzError::InvalidArgument(format!(
+            "joliet volume descriptor not found, path={}",
+            path.to_string_lossy()
+        )));
+    }
+    let root = descriptor.get(156..190).unwrap_or_default();
+    let mut directory = vec![0; le_u32(root, 10) as usize];
+    iso.read_exact_at(&mut directory, u64::from(le_u32(root, 2)) * SECTOR_SIZE)
+        .or_io_error("failed to read directory")?;
+    let file_identifier = identifier(name, false, true);
+    let mut offset = 0;
+    while let Some(length) = directory.get(offset).map(|length| usize::from(*length)) {
+        // records don't span sectors, rest of sector is zero
+        if length == 0 {
+            offset = (offset + 1).next_multiple_of(SECTOR_SIZE as usize);
+            continue;
+        }
+        let record = directory.get(offset..offset + length).unwrap_or_default();
+        offset += length;
+        let identifier_length = usize::from(record.get(32).copied().unwrap_or_default());
+        let flags = record.get(25).copied().unwrap_or_default();
+        if flags & FLAG_DIRECTORY == 0 && record.get(33..33 + identifier_length) == Some(file_identifier.as_slice()) {
+            let mut data = vec![0; le_u32(record, 10) as usize];
+            iso.read_exact_at(&mut data, u64::from(le_u32(record, 2)) * SECTOR_SIZE)
+                .or_io_error("failed to read iso file")?;
+            return Ok(Some(data));
+        }
+    }
+    Ok(None)
+}
+
+fn write_file(mut iso: &File, file: &FileExtent) -> Result<()> {
+    match file.source {
+        Source::Data(data) => iso.write_all_at(data, file.sector * SECTOR_SIZE).or_io_error("failed to write iso file"),
+        Source::Path(path) => {
+            let source = File::open(path).or_io_error("failed to open file")?;
+            iso.seek(SeekFrom::Start(file.sector * SECTOR_SIZE)).or_io_error("failed to seek iso")?;
+            let copied = io::copy(&mut source.take(file.size), &mut iso).or_io_error("failed to write iso file")?;
+            if copied != file.size {
+                return Err(VzError::InvalidArgument(format!(
+                    "file changed while writing iso, path={}",
+                    path.to_string_lossy()
+                )));
+            }
+            Ok(())
+        }
+    }
+}
+
+fn build_tree<'a>(nodes: &'a [IsoNode], files: &mut Vec<FileExtent<'a>>) -> Tree<'a> {
+    let mut tree = Tree { dirs: vec![], files: vec![] };
+    for node in nodes {
+        match node {
+            IsoNode::File { name, size, source } => {
+                tree.files.push(files.len());
+                files.push(FileExtent { name, size: *size, source, sector: 0 });
+            }
+            IsoNode::Dir { name, children } => tree.dirs.push((name, build_tree(children, files))),
+        }
+    }
+    tree
+}
+
+// directories in breadth first order with children sorted by identifier, which is path table order
+fn hierarchy<'a>(tree: &'a Tree<'a>, files: &[FileExtent], joliet: bool) -> Vec<Dir<'a>> {
+    let mut dirs =
+        vec![Dir { tree, identifier: vec![0], parent: 1, children: vec![], files: vec![], sector: 0, size: 0 }];
+    let mut index = 0;
+    while let Some(dir) = dirs.get(index) {
+        let file_names = dir.tree.files.iter().filter_map(|file| files.get(*file).map(|extent| (extent.name, false)));
+        let names: Vec<(&str, bool)> = dir.tree.dirs.iter().map(|(name, _)| (*name, true)).chain(file_names).collect();
+        let mut identifiers = unique_identifiers(&names, joliet).into_iter();
+        let mut sorted: Vec<(Vec<u8>, &Tree)> = dir
+            .tree
+            .dirs
+            .iter()
+            .zip(identifiers.by_ref())
+            .map(|((_, child), identifier)| (identifier, child))
+            .collect();
+        sorted.sort_by(|(identifier1, _), (identifier2, _)| identifier1.cmp(identifier2));
+        let dir_files: Vec<(usize, Vec<u8>)> = dir.tree.files.iter().copied().zip(identifiers).collect();
+        let first_child = dirs.len();
+        for (identifier, child) in sorted {
+            dirs.push(Dir {
+                tree: child,
+                identifier,
+                parent: index + 1,
+                children: vec![],
+                files: vec![],
+                sector: 0,
+                size: 0,
+            });
+        }
+        let children = (first_child..dirs.len()).collect();
+        if let Some(parent) = dirs.get_mut(index) {
+            parent.children = children;
+            parent.files = dir_files;
+        }
+        index += 1;
+    }
+    dirs
+}
+
+// ".", "..", then children sorted by identifier
+fn records(dirs: &[Dir], index: usize, files: &[FileExtent]) -> Vec<Record> {
+    let Some(dir) = dirs.get(index) else {
+        return vec![];
+    };
+    let parent = dirs.get(dir.parent - 1).unwrap_or(dir);
+    let mut children: Vec<Record> = dir
+        .children
+        .iter()
+        .filter_map(|child| dirs.get(*child))
+        .map(|child| Record {
+            identifier: child.identifier.clone(),
+            sector: child.sector,
+            size: child.size,
+            flags: FLAG_DIRECTORY,
+        })
+        .chain(dir.files.iter().filter_map(|(file, identifier)| {
+            files.get(*file).map(|file| Record {
+                identifier: identifier.clone(),
+                sector: file.sector,
+                size: file.size,
+                flags: 0,
+            })
+        }))
+        .collect();
+    children.sort_by(|record1, record2| record1.identifier.cmp(&record2.identifier));
+    let mut records = vec![
+        Record { identifier: vec![0], sector: dir.sector, size: dir.size, flags: FLAG_DIRECTORY },
+        Record { identifier: vec![1], sector: parent.sector, size: parent.size, flags: FLAG_DIRECTORY },
+    ];
+    records.extend(children);
+    records
+}
+
+// records must not cross sector boundary, directory size is multiple of sector size
+fn directory(records: &[Record], recorded_at: &[u64; 6]) -> Vec<u8> {
+    let mut directory = vec![];
+    for record in records {
+        let bytes = directory_record(record, recorded_at);
+        let remaining = SECTOR_SIZE as usize - directory.len() % SECTOR_SIZE as usize;
+        if bytes.len() > remaining {
+            directory.resize(directory.len() + remaining, 0);
+        }
+        directory.extend(bytes);
+    }
+    directory.resize((directory.len() as u64).div_ceil(SECTOR_SIZE) as usize * SECTOR_SIZE as usize, 0);
+    directory
+}
+
+fn directory_record(record: &Record, recorded_at: &[u64; 6]) -> Vec<u8> {
+    // record length is even, pad byte follows identifier of even length
+    let length = 33 + record.identifier.len() + (record.identifier.len() + 1) % 2;
+    let mut bytes = vec![0; length];
+    put(&mut bytes, 0, &[length as u8]);
+    put(&mut bytes, 2, &both_endian(record.sector, 4));
+    put(&mut bytes, 10, &both_endian(record.size, 4));
+    let [year, month, day, hour, minute, second] = recorded_at;
+    let date = [year.saturating_sub(1900), *month, *day, *hour, *minute, *second].map(|value| value as u8);
+    put(&mut bytes, 18, &date);
+    put(&mut bytes, 25, &[record.flags]);
+    put(&mut bytes, 28, &both_endian(1, 2));
+    put(&mut bytes, 32, &[record.identifier.len() as u8]);
+    put(&mut bytes, 33, &record.identifier);
+    bytes
+}
+
+fn path_table_size(dirs: &[Dir]) -> u64 {
+    dirs.iter().map(|dir| 8 + dir.identifier.len().next_multiple_of(2) as u64).sum()
+}
+
+fn path_table(dirs: &[Dir], big_endian: bool) -> Vec<u8> {
+    let bytes = if big_endian { be_bytes } else { le_bytes };
+    let mut table = vec![];
+    for dir in dirs {
+        table.extend([dir.identifier.len() as u8, 0]);
+        table.extend(bytes(dir.sector, 4));
+        table.extend(bytes(dir.parent as u64, 2));
+        table.extend(&dir.identifier);
+        table.resize(table.len().next_multiple_of(2), 0);
+    }
+    table
+}
+
+// primary volume descriptor, or supplementary volume descriptor with identifiers in UCS-2 for joliet
+fn volume_descriptor(volume: &VolumeDescriptor) -> Vec<u8> {
+    let text = |value: &str, length: usize| {
+        if volume.joliet {
+            padded(&ucs2(value, length / 2), length, &[0, b' '])
+        } else {
+            padded(value.as_bytes(), length, b" ")
+        }
+    };
+    let mut descriptor = vec![0; SECTOR_SIZE as usize];
+    put(&mut descriptor, 0, if volume.joliet { b"\x02CD001\x01" } else { b"\x01CD001\x01" });
+    put(&mut descriptor, 8, &text("", 32));
+    put(&mut descriptor, 40, &text(volume.volume_id, 32));
+    put(&mut descriptor, 80, &both_endian(volume.sectors, 4));
+    if volume.joliet {
+        put(&mut descriptor, 88, JOLIET_ESCAPE_SEQUENCE);
+    }
+    // volume set size, volume sequence number, logical block size
+    put(&mut descriptor, 120, &both_endian(1, 2));
+    put(&mut descriptor, 124, &both_endian(1, 2));
+    put(&mut descriptor, 128, &both_endian(SECTOR_SIZE, 2));
+    put(&mut descriptor, 132, &both_endian(volume.path_table_size, 4));
+    put(&mut descriptor, 140, &le_bytes(volume.l_path_table_sector, 4));
+    put(&mut descriptor, 148, &be_bytes(volume.m_path_table_sector, 4));
+    put(&mut descriptor, 156, volume.root);
+    // volume set, publisher, data preparer, application, copyright, abstract and bibliographic identifiers
+    put(&mut descriptor, 190, &text("", 128 * 4 + 37 * 3));
+    let [year, month, day, hour, minute, second] = volume.recorded_at;
+    let date = format!("{year:04}{month:02}{day:02}{hour:02}{minute:02}{second:02}00");
+    put(&mut descriptor, 813, date.as_bytes());
+    put(&mut descriptor, 830, date.as_bytes());
+    put(&mut descriptor, 847, b"0000000000000000");
+    put(&mut descriptor, 864, b"0000000000000000");
+    // file structure version
+    put(&mut descriptor, 881, &[1]);
+    descriptor
+}
+
+// primary: d-characters in upper case, e.g. user-data => USER-DATA.;1, which linux shows as user-data
+// joliet: UCS-2 big endian up to 64 characters including version, e.g. user-data;1
+fn identifier(name: &str, dir: bool, joliet: bool) -> Vec<u8> {
+    encode(&converted_name(name, dir, joliet), dir, joliet)
+}
+
+// names in same dir which collide after conversion get ~N suffix, e.g. Makefile and makefile => MAKEFILE.;1 and MAKEFILE~1.;1,
+// names are processed in sorted order, so result doesn't depend on dir listing order
+fn unique_identifiers(names: &[(&str, bool)], joliet: bool) -> Vec<Vec<u8>> {
+    let mut order: Vec<usize> = (0..names.len()).collect();
+    order.sort_by_key(|index| names.get(*index));
+    let mut identifiers = vec![vec![]; names.len()];
+    let mut used = HashSet::new();
+    for index in order {
+        let Some((name, dir)) = names.get(index).copied() else {
+            continue;
+        };
+        let name = converted_name(name, dir, joliet);
+        let mut identifier = encode(&name, dir, joliet);
+        let mut suffix = 1;
+        while used.contains(&identifier) {
+            identifier = encode(&mangled_name(&name, dir, joliet, suffix), dir, joliet);
+            suffix += 1;
+        }
+        used.insert(identifier.clone());
+        if let Some(slot) = identifiers.get_mut(index) {
+            *slot = identifier;
+        }
+    }
+    identifiers
+}
+
+// name without version in charset of hierarchy
+fn converted_name(name: &str, dir: bool, joliet: bool) -> String {
+    if joliet {
+        return name.chars().take(JOLIET_NAME_LENGTH).collect();
+    }
+    let mut name = name
+        .chars()
+        .take(PRIMARY_NAME_LENGTH)
+        .map(|char| if char.is_ascii_alphanumeric() || char == '-' || (char == '.' && !dir) { char } else { '_' })
+        .collect::<String>()
+        .to_ascii_uppercase();
+    if !dir && !name.contains('.') {
+        name.push('.');
+    }
+    name
+}
+
+// keep extension of file, shorten name to fit suffix
+fn mangled_name(name: &str, dir: bool, joliet: bool, suffix: usize) -> String {
+    let suffix = format!("~{suffix}");
+    let (stem, extension) = match name.rfind('.') {
+        Some(position) if !dir => name.split_at(position),
+        _ => (name, ""),
+    };
+    let max_length = if joliet { JOLIET_NAME_LENGTH } else { PRIMARY_NAME_LENGTH };
+    let stem: String = stem.chars().take(max_length.saturating_sub(suffix.len() + extension.chars().count())).collect();
+    format!("{stem}{suffix}{extension}")
+}
+
+fn encode(name: &str, dir: bool, joliet: bool) -> Vec<u8> {
+    let version = if dir { "" } else { ";1" };
+    if joliet {
+        ucs2(&format!("{name}{version}"), JOLIET_NAME_LENGTH + 2)
+    } else {
+        format!("{name}{version}").into_bytes()
+    }
+}
+
+// characters outside basic multilingual plane are replaced with _
+fn ucs2(value: &str, length: usize) -> Vec<u8> {
+    value
+        .chars()
+        .take(length)
+        .flat_map(|char| {
+            let code = u16::try_from(u32::from(char)).unwrap_or(u16::from(b'_'));
+            [(code >> 8) as u8, code as u8]
+        })
+        .collect()
+}
+
+fn padded(value: &[u8], length: usize, padding: &[u8]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = value.iter().take(length).copied().collect();
+    while bytes.len() < length {
+        bytes.extend(padding);
+    }
+    bytes.truncate(length);
+    bytes
+}
+
+fn both_endian(value: u64, length: usize) -> Vec<u8> {
+    let mut bytes = le_bytes(value, length);
+    bytes.extend(be_bytes(value, length));
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::env;
+    use std::os::unix::fs::symlink;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn decode(bytes: &[u8], joliet: bool) -> String {
+        if joliet {
+            let chars: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|pair| pair.iter().fold(0, |value, byte| (value << 8) | u16::from(*byte)))
+                .collect();
+            String::from_utf16_lossy(&chars)
+        } else {
+            String::from_utf8_lossy(bytes).to_string()
+        }
+    }
+
+    // read volume id and files by path from primary or joliet hierarchy
+    fn read(iso: &[u8], joliet: bool) -> (String, BTreeMap<String, Vec<u8>>) {
+        let sector = if joliet { JOLIET_VOLUME_DESCRIPTOR_SECTOR } else { PRIMARY_VOLUME_DESCRIPTOR_SECTOR };
+        let descriptor = &iso[(sector * SECTOR_SIZE) as usize..((sector + 1) * SECTOR_SIZE) as usize];
+        assert_eq!(2048, descriptor.len(), "descriptor takes one sector");
+        assert_eq!(b"CD001", &descriptor[1..6]);
+        assert_eq!(joliet, &descriptor[88..91] == JOLIET_ESCAPE_SEQUENCE, "joliet has escape sequence");
+        let volume_id = decode(&descriptor[40..72], joliet).trim_end().to_owned();
+        let mut files = BTreeMap::new();
+        read_dir(iso, &descriptor[156..190], "", joliet, &mut files);
+        (volume_id, files)
+    }
+
+    fn read_dir(iso: &[u8], dir_record: &[u8], dir_path: &str, joliet: bool, files: &mut BTreeMap<String, Vec<u8>>) {
+        let start = u64::from(le_u32(dir_record, 2)) * SECTOR_SIZE;
+        let end = start + u64::from(le_u32(dir_record, 10));
+        let mut offset = start;
+        while offset < end {
+            let length = u64::from(iso[offset as usize]);
+            if length == 0 {
+                offset = (offset + 1).next_multiple_of(SECTOR_SIZE);
+                continue;
+            }
+            let record = &iso[offset as usize..(offset + length) as usize];
+            offset += length;
+            let identifier = &record[33..33 + usize::from(record[32])];
+            if identifier == [0] || identifier == [1] {
+                continue;
+            }
+            let decoded = decode(identifier, joliet);
+            let name = decoded.trim_end_matches(";1");
+            let path = format!("{dir_path}/{}", if joliet { name } else { name.trim_end_matches('.') });
+            if record[25] & FLAG_DIRECTORY == 0 {
+                let data = (u64::from(le_u32(record, 2)) * SECTOR_SIZE) as usize;
+                files.insert(path, iso[data..data + u64::from(le_u32(record, 10)) as usize].to_vec());
+            } else {
+                read_dir(iso, record, &path, joliet, files);
+            }
+        }
+    }
+
+    #[test]
+    fn file_identifier() {
+        assert_eq!(b"USER-DATA.;1".to_vec(), identifier("user-data", false, false));
+        assert_eq!(b"README.MD;1".to_vec(), identifier("readme.md", false, false));
+        assert_eq!(b"A_B.;1".to_vec(), identifier("a b", false, false));
+        assert_eq!(b"CONF_D".to_vec(), identifier("conf.d", true, false));
+        assert_eq!(b"\x00a\x00;\x001".to_vec(), identifier("a", false, true));
+        assert_eq!(128, identifier(&"a".repeat(100), false, true).len(), "64 characters including version");
+        assert_eq!(124, identifier(&"a".repeat(100), true, true).len());
+    }
+
+    #[test]
+    fn write_and_read() {
+        let dir = env::temp_dir().join(Uuid::now_v7().to_string());
+        fs::create_dir_all(dir.join("source/certs/nested")).unwrap();
+        fs::write(dir.join("source/install.sh"), b"#!/bin/sh\necho ok\n").unwrap();
+        fs::write(dir.join("source/certs/Root CA.crt"), vec![b'c'; 5000]).unwrap();
+        fs::write(dir.join("source/certs/nested/empty"), b"").unwrap();
+        let mut nodes = scan_dir(&dir.join("source")).unwrap();
+        // enough records to span more than one directory sector
+        for index in 0..60 {
+            nodes.push(IsoNode::file(&format!("generated-file-{index:02}.txt"), index.to_string().into_bytes()));
+        }
+        let path = dir.join("out.iso");
+        write(&path, "config", &nodes).unwrap();
+        let iso = fs::read(&path).unwrap();
+
+        let (volume_id, files) = read(&iso, true);
+        assert_eq!("config", volume_id);
+        assert_eq!(63, files.len());
+        assert_eq!(b"#!/bin/sh\necho ok\n".to_vec(), files["/install.sh"]);
+        assert_eq!(vec![b'c'; 5000], files["/certs/Root CA.crt"]);
+        assert!(files["/certs/nested/empty"].is_empty(), "empty file");
+        assert_eq!(b"59".to_vec(), files["/generated-file-59.txt"]);
+
+        let (primary_volume_id, primary_files) = read(&iso, false);
+        assert_eq!("config", primary_volume_id);
+        assert_eq!(63, primary_files.len());
+        assert_eq!(b"#!/bin/sh\necho ok\n".to_vec(), primary_files["/INSTALL.SH"]);
+        assert_eq!(vec![b'c'; 5000], primary_files["/CERTS/ROOT_CA.CRT"]);
+        assert!(primary_files["/CERTS/NESTED/EMPTY"].is_empty(), "empty file");
+
+        assert_eq!(Some(b"59".to_vec()), read_root_file(&path, "generated-file-59.txt").unwrap());
+        assert_eq!(None, read_root_file(&path, "certs").unwrap(), "dir is not file");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unique_identifiers() {
+        let decoded = |names: &[(&str, bool)], joliet: bool| -> Vec<String> {
+            super::unique_identifiers(names, joliet).iter().map(|identifier| decode(identifier, joliet)).collect()
+        };
+        let names = [
+            ("makefile", false),
+            ("Makefile", false),
+            ("a b", false),
+            ("a_b", false),
+            ("conf.d", true),
+            ("conf_d", true),
+        ];
+        assert_eq!(
+            vec!["MAKEFILE~1.;1", "MAKEFILE.;1", "A_B.;1", "A_B~1.;1", "CONF_D", "CONF_D~1"],
+            decoded(&names, false)
+        );
+
+        let short_prefix = "a".repeat(40);
+        let primary = [(short_prefix.as_str(), false), (&format!("{short_prefix}b"), false)];
+        assert_eq!(
+            vec![format!("{}.;1", "A".repeat(30)), format!("{}~1.;1", "A".repeat(27))],
+            decoded(&primary, false)
+        );
+
+        let long_prefix = "a".repeat(70);
+        let joliet = [(long_prefix.as_str(), false), (&format!("{long_prefix}b"), false)];
+        assert_eq!(vec![format!("{};1", "a".repeat(62)), format!("{}~1;1", "a".repeat(60))], decoded(&joliet, true));
+    }
+
+    #[test]
+    fn scan_dir_with_symlink_loop() {
+        let dir = env::temp_dir().join(Uuid::now_v7().to_string());
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("nested/file"), b"data").unwrap();
+        symlink(&dir, dir.join("nested/loop")).unwrap();
+        symlink(dir.join("nested/file"), dir.join("link")).unwrap();
+
+        let mut names: Vec<String> = scan_dir(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|node| match node {
+                IsoNode::File { name, .. } => name,
+                IsoNode::Dir { name, children } => format!("{name}/{}", children.len()),
+            })
+            .collect();
+        names.sort();
+        assert_eq!(vec!["link", "nested/1"], names, "symlinked file is followed, symlinked dir is skipped");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn duplicate_name() {
+        let dir = env::temp_dir().join(Uuid::now_v7().to_string());
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.iso");
+        let nodes = [IsoNode::file("a b", b"1".to_vec()), IsoNode::file("a_b", b"2".to_vec())];
+        write(&path, "test", &nodes).unwrap();
+        let iso = fs::read(&path).unwrap();
+
+        let (_, primary_files) = read(&iso, false);
+        assert_eq!(Some(&b"1".to_vec()), primary_files.get("/A_B"));
+        assert_eq!(Some(&b"2".to_vec()), primary_files.get("/A_B~1"));
+        let (_, joliet_files) = read(&iso, true);
+        assert_eq!(Some(&b"1".to_vec()), joliet_files.get("/a b"));
+        assert_eq!(Some(&b"2".to_vec()), joliet_files.get("/a_b"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,11 +3,22 @@ use std::fmt;
 use serde::Serialize;
 use serde::de;
 
-pub fn from_json<'a, T>(json: &'a str) -> T
+use crate::error::Result;
+use crate::error::VzError;
+
+pub fn from_json<'a, T>(json: &'a str) -> Result<T>
 where
     T: de::Deserialize<'a>,
 {
-    serde_json::from_str(json).unwrap_or_else(|err| panic!("failed to deserialize, json={json}, err={err}"))
+    serde_json::from_str(json).map_err(|err| VzError::InvalidConfig(format!("failed to deserialize json, err={err}")))
+}
+
+pub fn from_value<T>(value: serde_json::Value) -> Result<T>
+where
+    T: de::DeserializeOwned,
+{
+    serde_json::from_value(value)
+        .map_err(|err| VzError::InvalidConfig(format!("failed to deserialize json, err={err}")))
 }
 
 pub fn to_json_pretty<T>(object: &T) -> String
@@ -18,6 +29,13 @@ where
         .unwrap_or_else(|err| panic!("failed to serialize, object={object:?}, err={err}"))
 }
 
+pub fn to_value<T>(object: &T) -> serde_json::Value
+where
+    T: Serialize + fmt::Debug,
+{
+    serde_json::to_value(object).unwrap_or_else(|err| panic!("failed to serialize, object={object:?}, err={err}"))
+}
+
 pub fn to_json_value<T>(enum_value: &T) -> String
 where
     T: Serialize + fmt::Debug,
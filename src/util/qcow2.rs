@@ -0,0 +1,324 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::os::unix::fs::FileExt as _;
+
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::bytes::be_bytes;
+use crate::util::bytes::be_u32;
+use crate::util::bytes::be_u64;
+use crate::util::bytes::put;
+use crate::util::inflate;
+use crate::util::sparse_file;
+
+// https://gitlab.com/qemu-project/qemu/-/blob/master/docs/interop/qcow2.txt
+pub const MAGIC: &[u8] = b"QFI\xfb";
+const HEADER_LENGTH: u64 = 104;
+// 64KiB, same as qemu-img default
+const CLUSTER_BITS: u32 = 16;
+// 16 bits refcount
+const REFCOUNT_ORDER: u32 = 4;
+const OFFSET_MASK: u64 = 0x00ff_ffff_ffff_fe00;
+const COPIED: u64 = 1 << 63;
+const COMPRESSED: u64 = 1 << 62;
+// v3 only, cluster reads as zero
+const ZERO: u64 = 1;
+// dirty bit only means refcounts may be stale, which is not used by reader
+const INCOMPATIBLE_DIRTY: u64 = 1;
+
+struct Header {
+    version: u32,
+    cluster_bits: u32,
+    size: u64,
+    l1_size: u32,
+    l1_table_offset: u64,
+}
+
+// return virtual disk size
+pub fn virtual_size(file: &File) -> Result<u64> {
+    Ok(read_header(file)?.size)
+}
+
+// convert qcow2 to sparse raw image, unallocated and zero clusters become holes
+pub fn to_raw(from: &File, to: &File) -> Result<()> {
+    let header = read_header(from)?;
+    let cluster_size = 1 << header.cluster_bits;
+    let file_len = from.metadata().or_io_error("failed to read file metadata")?.len();
+    to.set_len(header.size).or_io_error("failed to resize file")?;
+
+    let mut l1 = vec![0; header.l1_size as usize * 8];
+    from.read_exact_at(&mut l1, header.l1_table_offset).or_io_error("failed to read qcow2 l1 table")?;
+    let mut l2 = vec![0; cluster_size as usize];
+    let mut cluster = vec![];
+    let mut guest_offset = 0;
+    for l1_entry in l1.chunks_exact(8).map(|bytes| be_u64(bytes, 0)) {
+        let l2_offset = l1_entry & OFFSET_MASK;
+        if l2_offset == 0 {
+            guest_offset += cluster_size * cluster_size / 8;
+            continue;
+        }
+        from.read_exact_at(&mut l2, l2_offset).or_io_error("failed to read qcow2 l2 table")?;
+        for l2_entry in l2.chunks_exact(8).map(|bytes| be_u64(bytes, 0)) {
+            if guest_offset >= header.size {
+                break;
+            }
+            let length = (header.size - guest_offset).min(cluster_size) as usize;
+            if l2_entry & COMPRESSED != 0 {
+                cluster = read_compressed_cluster(from, file_len, header.cluster_bits, l2_entry)?;
+                cluster.resize(length, 0);
+            } else if l2_entry & OFFSET_MASK == 0 || (header.version >= 3 && l2_entry & ZERO != 0) {
+                cluster.clear();
+            } else {
+                cluster.resize(length, 0);
+                from.read_exact_at(&mut cluster, l2_entry & OFFSET_MASK).or_io_error("failed to read qcow2 cluster")?;
+            }
+            if cluster.iter().any(|byte| *byte != 0) {
+                to.write_all_at(&cluster, guest_offset).or_io_error("failed to write file")?;
+            }
+            guest_offset += cluster_size;
+        }
+    }
+    to.sync_all().or_io_error("failed to sync file")
+}
+
+// compressed cluster descriptor stores host offset and number of additional 512 bytes sectors
+fn read_compressed_cluster(file: &File, file_len: u64, cluster_bits: u32, entry: u64) -> Result<Vec<u8>> {
+    let offset_bits = 62 - (cluster_bits - 8);
+    let offset = entry & ((1 << offset_bits) - 1);
+    let sectors = (entry >> offset_bits) & ((1 << (cluster_bits - 8)) - 1);
+    // compressed data may end before last sector, which can be end of file
+    let size = ((sectors + 1) * 512 - (offset & 511)).min(file_len.saturating_sub(offset));
+    let mut data = vec![0; size as usize];
+    file.read_exact_at(&mut data, offset).or_io_error("failed to read qcow2 compressed cluster")?;
+    inflate::inflate(&data, 1 << cluster_bits)
+}
+
+fn read_header(file: &File) -> Result<Header> {
+    let mut block = [0; HEADER_LENGTH as usize];
+    file.read_exact_at(&mut block, 0).or_io_error("failed to read qcow2 header")?;
+    if block.get(..4) != Some(MAGIC) {
+        return Err(invalid_qcow2("invalid magic"));
+    }
+    let header = Header {
+        version: be_u32(&block, 4),
+        cluster_bits: be_u32(&block, 20),
+        size: be_u64(&block, 24),
+        l1_size: be_u32(&block, 36),
+        l1_table_offset: be_u64(&block, 40),
+    };
+    if header.version != 2 && header.version != 3 {
+        return Err(invalid_qcow2(&format!("unsupported version, version={}", header.version)));
+    }
+    if !(9..=21).contains(&header.cluster_bits) {
+        return Err(invalid_qcow2(&format!("invalid cluster bits, cluster_bits={}", header.cluster_bits)));
+    }
+    if be_u64(&block, 8) != 0 {
+        return Err(invalid_qcow2("backing file is not supported, run 'qemu-img convert' to flatten image"));
+    }
+    if be_u32(&block, 32) != 0 {
+        return Err(invalid_qcow2("encrypted image is not supported"));
+    }
+    let incompatible_features = if header.version >= 3 { be_u64(&block, 72) } else { 0 };
+    if incompatible_features & !INCOMPATIBLE_DIRTY != 0 {
+        return Err(invalid_qcow2(&format!(
+            "unsupported features, e.g. zstd compression or external data file, incompatible_features={incompatible_features:#x}"
+        )));
+    }
+    Ok(header)
+}
+
+// write qcow2 v3 from raw image, only clusters with data are allocated, layout is
+// header, l1 table, l2 tables and data clusters in order of guest offset, refcount table and blocks
+pub fn from_raw(from: &File, to: &File) -> Result<()> {
+    let size = from.metadata().or_io_error("failed to read file metadata")?.len();
+    let cluster_size = 1 << CLUSTER_BITS;
+    let l2_entries = cluster_size / 8;
+    let l1_size = size.div_ceil(cluster_size).div_ceil(l2_entries);
+    let l1_clusters = (l1_size * 8).div_ceil(cluster_size).max(1);
+
+    // next free cluster index
+    let mut next = 1 + l1_clusters;
+    let mut l2_tables: BTreeMap<u64, (u64, Vec<u64>)> = BTreeMap::new();
+    let mut buffer = vec![];
+    // data ranges may share cluster, e.g. 0-4K and 32K-36K, start after last converted cluster
+    let mut next_cluster_index = 0;
+    for (start, end) in sparse_file::data_ranges(from)? {
+        let mut cluster_index = (start / cluster_size).max(next_cluster_index);
+        while cluster_index * cluster_size < end {
+            let offset = cluster_index * cluster_size;
+            buffer.resize((size - offset).min(cluster_size) as usize, 0);
+            from.read_exact_at(&mut buffer, offset).or_io_error("failed to read file")?;
+            if buffer.iter().any(|byte| *byte != 0) {
+                let (_, l2) = l2_tables.entry(cluster_index / l2_entries).or_insert_with(|| {
+                    next += 1;
+                    ((next - 1) * cluster_size, vec![0; l2_entries as usize])
+                });
+                if let Some(entry) = l2.get_mut((cluster_index % l2_entries) as usize) {
+                    *entry = (next * cluster_size) | COPIED;
+                }
+                to.write_all_at(&buffer, next * cluster_size).or_io_error("failed to write qcow2 cluster")?;
+                next += 1;
+            }
+            cluster_index += 1;
+        }
+        next_cluster_index = cluster_index;
+    }
+
+    let mut l1 = vec![0; (l1_clusters * cluster_size) as usize];
+    for (l1_index, (l2_offset, l2)) in &l2_tables {
+        put(&mut l1, (l1_index * 8) as usize, &be_bytes(l2_offset | COPIED, 8));
+        let bytes: Vec<u8> = l2.iter().flat_map(|entry| be_bytes(*entry, 8)).collect();
+        to.write_all_at(&bytes, *l2_offset).or_io_error("failed to write qcow2 l2 table")?;
+    }
+    to.write_all_at(&l1, cluster_size).or_io_error("failed to write qcow2 l1 table")?;
+
+    // refcount table and blocks also need refcounts, grow until they cover themselves
+    let refcounts_per_block = (cluster_size * 8) >> REFCOUNT_ORDER;
+    let mut refcount_blocks = 0;
+    let mut refcount_table_clusters = 0;
+    loop {
+        let total = next + refcount_table_clusters + refcount_blocks;
+        let blocks = total.div_ceil(refcounts_per_block);
+        let table_clusters = (blocks * 8).div_ceil(cluster_size);
+        if blocks == refcount_blocks && table_clusters == refcount_table_clusters {
+            break;
+        }
+        refcount_blocks = blocks;
+        refcount_table_clusters = table_clusters;
+    }
+    let refcount_table_offset = next * cluster_size;
+    let total = next + refcount_table_clusters + refcount_blocks;
+    let mut refcount_table = vec![0; (refcount_table_clusters * cluster_size) as usize];
+    for block in 0..refcount_blocks {
+        let block_offset = (next + refcount_table_clusters + block) * cluster_size;
+        put(&mut refcount_table, (block * 8) as usize, &be_bytes(block_offset, 8));
+        let count = (total - block * refcounts_per_block).min(refcounts_per_block);
+        let mut refcounts = vec![0; cluster_size as usize];
+        for index in 0..count {
+            put(&mut refcounts, (index * 2) as usize, &be_bytes(1, 2));
+        }
+        to.write_all_at(&refcounts, block_offset).or_io_error("failed to write qcow2 refcount block")?;
+    }
+    to.write_all_at(&refcount_table, refcount_table_offset).or_io_error("failed to write qcow2 refcount table")?;
+
+    let mut header = vec![0; cluster_size as usize];
+    put(&mut header, 0, MAGIC);
+    put(&mut header, 4, &be_bytes(3, 4));
+    put(&mut header, 20, &be_bytes(u64::from(CLUSTER_BITS), 4));
+    put(&mut header, 24, &be_bytes(size, 8));
+    put(&mut header, 36, &be_bytes(l1_size, 4));
+    put(&mut header, 40, &be_bytes(cluster_size, 8));
+    put(&mut header, 48, &be_bytes(refcount_table_offset, 8));
+    put(&mut header, 56, &be_bytes(refcount_table_clusters, 4));
+    put(&mut header, 96, &be_bytes(u64::from(REFCOUNT_ORDER), 4));
+    put(&mut header, 100, &be_bytes(HEADER_LENGTH, 4));
+    // header extension end marker follows header, which is already zero
+    to.write_all_at(&header, 0).or_io_error("failed to write qcow2 header")?;
+    to.sync_all().or_io_error("failed to sync file")
+}
+
+fn invalid_qcow2(message: &str) -> VzError {
+    VzError::InvalidArgument(format!("invalid qcow2 image, {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn open(path: &Path) -> File {
+        File::options().read(true).write(true).create(true).truncate(true).open(path).unwrap()
+    }
+
+    #[test]
+    fn convert() {
+        let dir = env::temp_dir().join(Uuid::now_v7().to_string());
+        fs::create_dir_all(&dir).unwrap();
+        let raw = open(&dir.join("disk.img"));
+        raw.set_len(1024 * 1024 * 1024 + 1000).unwrap();
+        raw.write_all_at(b"head", 0).unwrap();
+        raw.write_all_at(b"middle", 600 * 1024 * 1024 + 10).unwrap();
+        raw.write_all_at(b"tail", 1024 * 1024 * 1024 + 996).unwrap();
+
+        let qcow2 = open(&dir.join("disk.qcow2"));
+        from_raw(&raw, &qcow2).unwrap();
+        assert_eq!(1024 * 1024 * 1024 + 1000, virtual_size(&qcow2).unwrap());
+        // header, l1, 3 l2 tables, 3 data clusters, refcount table and block
+        assert_eq!(10 * 64 * 1024, qcow2.metadata().unwrap().len());
+
+        let converted = open(&dir.join("converted.img"));
+        to_raw(&qcow2, &converted).unwrap();
+        assert_eq!(fs::read(dir.join("disk.img")).unwrap(), fs::read(dir.join("converted.img")).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn convert_ranges_in_same_cluster() {
+        let dir = env::temp_dir().join(Uuid::now_v7().to_string());
+        fs::create_dir_all(&dir).unwrap();
+        let raw = open(&dir.join("disk.img"));
+        raw.set_len(1024 * 1024).unwrap();
+        raw.write_all_at(&[1; 4096], 0).unwrap();
+        raw.write_all_at(&[2; 4096], 32 * 1024).unwrap();
+
+        let qcow2 = open(&dir.join("disk.qcow2"));
+        from_raw(&raw, &qcow2).unwrap();
+        // header, l1, l2 table, 1 data cluster, refcount table and block
+        assert_eq!(6 * 64 * 1024, qcow2.metadata().unwrap().len());
+
+        let converted = open(&dir.join("converted.img"));
+        to_raw(&qcow2, &converted).unwrap();
+        assert_eq!(fs::read(dir.join("disk.img")).unwrap(), fs::read(dir.join("converted.img")).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_compressed_cluster() {
+        let dir = env::temp_dir().join(Uuid::now_v7().to_string());
+        fs::create_dir_all(&dir).unwrap();
+        let raw = open(&dir.join("disk.img"));
+        raw.write_all_at(&vec![1; 64 * 1024], 0).unwrap();
+        let qcow2 = open(&dir.join("disk.qcow2"));
+        from_raw(&raw, &qcow2).unwrap();
+
+        // replace data cluster with compressed cluster of "hello hello hello hello" at end of file
+        let compressed = [0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x27, 0x01];
+        let offset = qcow2.metadata().unwrap().len() + 100;
+        qcow2.write_all_at(&compressed, offset).unwrap();
+        let offset_bits = 62 - (CLUSTER_BITS - 8);
+        qcow2.write_all_at(&be_bytes(COMPRESSED | offset, 8), 2 * 64 * 1024).unwrap();
+        assert!(offset < 1 << offset_bits, "offset fits in descriptor");
+
+        let converted = open(&dir.join("converted.img"));
+        to_raw(&qcow2, &converted).unwrap();
+        let mut expected = b"hello hello hello hello".to_vec();
+        expected.resize(64 * 1024, 0);
+        assert_eq!(expected, fs::read(dir.join("converted.img")).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_invalid_header() {
+        let dir = env::temp_dir().join(Uuid::now_v7().to_string());
+        fs::create_dir_all(&dir).unwrap();
+        let file = open(&dir.join("disk.qcow2"));
+        let mut header = vec![0; 512];
+        put(&mut header, 0, MAGIC);
+        put(&mut header, 4, &be_bytes(3, 4));
+        put(&mut header, 20, &be_bytes(16, 4));
+        put(&mut header, 8, &be_bytes(512, 8));
+        file.write_all_at(&header, 0).unwrap();
+        assert!(virtual_size(&file).is_err(), "backing file is not supported");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt::Write as _;
+
+#[rustfmt::skip]
+const K: [u32; 64] = [
+    0x428a_2f98, 0x7137_4491, 0xb5c0_fbcf, 0xe9b5_dba5, 0x3956_c25b, 0x59f1_11f1, 0x923f_82a4, 0xab1c_5ed5,
+    0xd807_aa98, 0x1283_5b01, 0x2431_85be, 0x550c_7dc3, 0x72be_5d74, 0x80de_b1fe, 0x9bdc_06a7, 0xc19b_f174,
+    0xe49b_69c1, 0xefbe_4786, 0x0fc1_9dc6, 0x240c_a1cc, 0x2de9_2c6f, 0x4a74_84aa, 0x5cb0_a9dc, 0x76f9_88da,
+    0x983e_5152, 0xa831_c66d, 0xb003_27c8, 0xbf59_7fc7, 0xc6e0_0bf3, 0xd5a7_9147, 0x06ca_6351, 0x1429_2967,
+    0x27b7_0a85, 0x2e1b_2138, 0x4d2c_6dfc, 0x5338_0d13, 0x650a_7354, 0x766a_0abb, 0x81c2_c92e, 0x9272_2c85,
+    0xa2bf_e8a1, 0xa81a_664b, 0xc24b_8b70, 0xc76c_51a3, 0xd192_e819, 0xd699_0624, 0xf40e_3585, 0x106a_a070,
+    0x19a4_c116, 0x1e37_6c08, 0x2748_774c, 0x34b0_bcb5, 0x391c_0cb3, 0x4ed8_aa4a, 0x5b9c_ca4f, 0x682e_6ff3,
+    0x748f_82ee, 0x78a5_636f, 0x84c8_7814, 0x8cc7_0208, 0x90be_fffa, 0xa450_6ceb, 0xbef9_a3f7, 0xc671_78f2,
+];
+
+const INITIAL_STATE: [u32; 8] =
+    [0x6a09_e667, 0xbb67_ae85, 0x3c6e_f372, 0xa54f_f53a, 0x510e_527f, 0x9b05_688c, 0x1f83_d9ab, 0x5be0_cd19];
+
+// FIPS 180-4, used to verify archive content
+pub struct Sha256 {
+    state: [u32; 8],
+    block: [u8; 64],
+    block_len: usize,
+    total_len: u64,
+}
+
+impl Sha256 {
+    pub fn new() -> Self {
+        Sha256 { state: INITIAL_STATE, block: [0; 64], block_len: 0, total_len: 0 }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+        while !data.is_empty() {
+            let size = (64 - self.block_len).min(data.len());
+            let (head, tail) = data.split_at(size);
+            for (target, byte) in self.block.iter_mut().skip(self.block_len).zip(head) {
+                *target = *byte;
+            }
+            self.block_len += size;
+            data = tail;
+            if self.block_len == 64 {
+                compress(&mut self.state, &self.block);
+                self.block_len = 0;
+            }
+        }
+    }
+
+    // return hex string
+    pub fn finish(mut self) -> String {
+        let bit_len = self.total_len * 8;
+        self.update(&[0x80]);
+        while self.block_len != 56 {
+            self.update(&[0]);
+        }
+        let len_bytes: [u8; 8] = [56, 48, 40, 32, 24, 16, 8, 0].map(|shift| (bit_len >> shift) as u8);
+        self.update(&len_bytes);
+
+        let mut hex = String::with_capacity(64);
+        for word in self.state {
+            write!(hex, "{word:08x}").unwrap_or_else(|err| panic!("failed to write string, err={err}"));
+        }
+        hex
+    }
+}
+
+// variable names follow the spec
+#[allow(clippy::many_single_char_names)]
+fn compress(state: &mut [u32; 8], block: &[u8; 64]) {
+    // rolling message schedule, words[0] is word of current round
+    let mut words = [0; 16];
+    for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+        *word = bytes.iter().fold(0, |value, byte| (value << 8) | u32::from(*byte));
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for k in K {
+        let [w0, w1, _, _, _, _, _, _, _, w9, _, _, _, _, w14, _] = words;
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let choice = (e & f) ^ (!e & g);
+        let temp1 = h.wrapping_add(s1).wrapping_add(choice).wrapping_add(k).wrapping_add(w0);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let majority = (a & b) ^ (a & c) ^ (b & c);
+        let temp2 = s0.wrapping_add(majority);
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(temp1);
+        d = c;
+        c = b;
+        b = a;
+        a = temp1.wrapping_add(temp2);
+
+        let sigma0 = w1.rotate_right(7) ^ w1.rotate_right(18) ^ (w1 >> 3);
+        let sigma1 = w14.rotate_right(17) ^ w14.rotate_right(19) ^ (w14 >> 10);
+        words.rotate_left(1);
+        if let Some(last) = words.last_mut() {
+            *last = w0.wrapping_add(sigma0).wrapping_add(w9).wrapping_add(sigma1);
+        }
+    }
+    for (value, updated) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *value = value.wrapping_add(updated);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha256(data: &[u8]) -> String {
+        let mut sha256 = Sha256::new();
+        sha256.update(data);
+        sha256.finish()
+    }
+
+    #[test]
+    fn digest() {
+        assert_eq!("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855", sha256(b""));
+        assert_eq!("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad", sha256(b"abc"));
+        assert_eq!(
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")
+        );
+    }
+
+    #[test]
+    fn digest_in_chunks() {
+        let data = vec![b'a'; 1_000_000];
+        let mut sha256 = Sha256::new();
+        for chunk in data.chunks(997) {
+            sha256.update(chunk);
+        }
+        assert_eq!("cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0", sha256.finish());
+    }
+}
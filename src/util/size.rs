@@ -0,0 +1,137 @@
+// binary units are 1024 based: K, M, G, T, KiB, MiB, GiB, TiB
+// decimal units are 1000 based: KB, MB, GB, TB
+pub const KIB: u64 = 1024;
+pub const MIB: u64 = 1024 * KIB;
+pub const GIB: u64 = 1024 * MIB;
+pub const TIB: u64 = 1024 * GIB;
+pub const KB: u64 = 1000;
+pub const MB: u64 = 1000 * KB;
+pub const GB: u64 = 1000 * MB;
+pub const TB: u64 = 1000 * GB;
+
+const UNITS: [(&str, u64); 13] = [
+    ("b", 1),
+    ("k", KIB),
+    ("m", MIB),
+    ("g", GIB),
+    ("t", TIB),
+    ("kib", KIB),
+    ("mib", MIB),
+    ("gib", GIB),
+    ("tib", TIB),
+    ("kb", KB),
+    ("mb", MB),
+    ("gb", GB),
+    ("tb", TB),
+];
+
+// ram size, number without unit is in GiB for backward compatibility, must be multiple of MiB, e.g. 1.3G is rejected
+pub fn parse_ram(value: &str) -> Result<u64, String> {
+    let size = parse(value, GIB)?;
+    if !size.is_multiple_of(MIB) {
+        return Err(format!("ram size must be multiple of 1MiB, value={value}"));
+    }
+    Ok(size)
+}
+
+// disk size, number without unit is in GB for backward compatibility
+pub fn parse_disk(value: &str) -> Result<u64, String> {
+    parse(value, GB)
+}
+
+// e.g. 512M, 1.5G, 2GiB, 100GB, case insensitive
+pub fn parse(value: &str, default_unit: u64) -> Result<u64, String> {
+    let value = value.trim();
+    let unit_start = value.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(unit_start);
+    let unit = if unit.is_empty() {
+        default_unit
+    } else {
+        let unit = unit.to_ascii_lowercase();
+        UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .map(|(_, size)| *size)
+            .ok_or_else(|| format!("unknown size unit, value={value}"))?
+    };
+
+    let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if integer.is_empty() || fraction.len() > 9 || !integer.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+    {
+        return Err(format!("invalid size, value={value}"));
+    }
+    let overflow = || format!("size is too large, value={value}");
+    let integer: u64 = integer.parse().map_err(|_err| overflow())?;
+
+    // fraction is rounded down to bytes
+    let fraction = if fraction.is_empty() {
+        0
+    } else {
+        let digits: u128 = fraction.parse().map_err(|_err| format!("invalid size, value={value}"))?;
+        let fraction = digits * u128::from(unit) / u128::pow(10, fraction.len() as u32);
+        u64::try_from(fraction).map_err(|_err| overflow())?
+    };
+    let size = integer.checked_mul(unit).and_then(|size| size.checked_add(fraction)).ok_or_else(overflow)?;
+    if size == 0 {
+        return Err(format!("size must be positive, value={value}"));
+    }
+    Ok(size)
+}
+
+// e.g. 1.00GiB
+pub fn format_binary(bytes: u64) -> String {
+    format(bytes, &[("TiB", TIB), ("GiB", GIB), ("MiB", MIB), ("KiB", KIB)])
+}
+
+// e.g. 50.00GB
+pub fn format_decimal(bytes: u64) -> String {
+    format(bytes, &[("TB", TB), ("GB", GB), ("MB", MB), ("KB", KB)])
+}
+
+fn format(bytes: u64, units: &[(&str, u64)]) -> String {
+    for (name, size) in units {
+        if bytes >= *size {
+            return format!("{:.2}{name}", bytes as f64 / *size as f64);
+        }
+    }
+    format!("{bytes}B")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_size() {
+        assert_eq!(Ok(512 * MIB), parse("512M", GIB));
+        assert_eq!(Ok(1536 * MIB), parse("1.5G", GIB));
+        assert_eq!(Ok(2 * GIB), parse("2GiB", GIB));
+        assert_eq!(Ok(2 * GIB), parse("2gib", GIB));
+        assert_eq!(Ok(100 * GB), parse("100GB", GIB));
+        assert_eq!(Ok(1234), parse("1234b", GIB));
+        assert_eq!(Ok(4 * GIB), parse_ram("4"));
+        assert_eq!(Ok(1536 * MIB), parse_ram("1.5G"));
+        assert_eq!(Ok(50 * GB), parse_disk("50"));
+    }
+
+    #[test]
+    fn parse_invalid_size() {
+        assert!(parse("", GIB).is_err(), "empty");
+        assert!(parse("G", GIB).is_err(), "no number");
+        assert!(parse("1.5.0G", GIB).is_err(), "invalid number");
+        assert!(parse("-1G", GIB).is_err(), "negative");
+        assert!(parse("0", GIB).is_err(), "zero");
+        assert!(parse("1X", GIB).is_err(), "unknown unit");
+        assert!(parse("99999999999T", GIB).is_err(), "overflow");
+        assert!(parse_ram("1.3G").is_err(), "ram is not multiple of MiB");
+    }
+
+    #[test]
+    fn format_size() {
+        assert_eq!("1.00GiB", format_binary(GIB));
+        assert_eq!("512.00MiB", format_binary(512 * MIB));
+        assert_eq!("50.00GB", format_decimal(50 * GB));
+        assert_eq!("1.50TB", format_decimal(1500 * GB));
+        assert_eq!("100B", format_decimal(100));
+    }
+}
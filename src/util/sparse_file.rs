@@ -0,0 +1,190 @@
+use std::fs::File;
+use std::io;
+use std::os::fd::AsRawFd as _;
+use std::os::unix::fs::FileExt as _;
+use std::path::Path;
+
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+
+const BUFFER_SIZE: usize = 1024 * 1024;
+// multiple of file system block size (APFS is 4K)
+const PUNCH_BLOCK_SIZE: u64 = 64 * 1024;
+
+// copy file without inflating holes, use copy-on-write clone if file system supports (e.g. APFS)
+pub fn copy(from: &Path, to: &Path) -> Result<()> {
+    if clone_file(from, to) {
+        return Ok(());
+    }
+
+    let source = File::open(from).or_io_error("failed to open file")?;
+    let target = File::create_new(to).or_io_error("failed to create file")?;
+    let len = source.metadata().or_io_error("failed to read file metadata")?.len();
+    target.set_len(len).or_io_error("failed to resize file")?;
+
+    let mut buffer = vec![];
+    for (start, end) in data_ranges(&source)? {
+        let mut offset = start;
+        while offset < end {
+            let size = usize::try_from(end - offset).map_or(BUFFER_SIZE, |size| size.min(BUFFER_SIZE));
+            buffer.resize(size, 0);
+            source.read_exact_at(&mut buffer, offset).or_io_error("failed to read file")?;
+            // skip zero blocks, so allocated but empty blocks become holes
+            if buffer.iter().any(|byte| *byte != 0) {
+                target.write_all_at(&buffer, offset).or_io_error("failed to write file")?;
+            }
+            offset += size as u64;
+        }
+    }
+    target.sync_all().or_io_error("failed to sync file")
+}
+
+// punch holes in zero filled blocks, e.g. blocks freed by guest, return punched size
+pub fn punch_zero_blocks(path: &Path) -> Result<u64> {
+    let file = File::options().read(true).write(true).open(path).or_io_error("failed to open file")?;
+    let mut buffer = vec![0; PUNCH_BLOCK_SIZE as usize];
+    let mut punched = 0;
+    for (start, end) in data_ranges(&file)? {
+        // only punch whole blocks, file system requires offset and length aligned to block size
+        let mut offset = start - start % PUNCH_BLOCK_SIZE;
+        let mut zero_start = None;
+        while offset < end {
+            let zero = offset + PUNCH_BLOCK_SIZE <= end && {
+                file.read_exact_at(&mut buffer, offset).or_io_error("failed to read file")?;
+                buffer.iter().all(|byte| *byte == 0)
+            };
+            if zero {
+                zero_start.get_or_insert(offset);
+            } else if let Some(zero_start) = zero_start.take() {
+                punch_hole(&file, zero_start, offset - zero_start)?;
+                punched += offset - zero_start;
+            }
+            offset += PUNCH_BLOCK_SIZE;
+        }
+        if let Some(zero_start) = zero_start {
+            let length = offset.min(end) - zero_start;
+            punch_hole(&file, zero_start, length)?;
+            punched += length;
+        }
+    }
+    file.sync_all().or_io_error("failed to sync file")?;
+    Ok(punched)
+}
+
+// return [start, end) of data regions, holes are skipped
+pub fn data_ranges(file: &File) -> Result<Vec<(u64, u64)>> {
+    let len = file.metadata().or_io_error("failed to read file metadata")?.len();
+    let mut ranges = vec![];
+    let mut offset = 0;
+    while offset < len {
+        let Some(start) = seek(file, offset, libc::SEEK_DATA)? else {
+            break;
+        };
+        let end = seek(file, start, libc::SEEK_HOLE)?.unwrap_or(len);
+        ranges.push((start, end));
+        offset = end;
+    }
+    Ok(ranges)
+}
+
+// return None if there is no more data after offset
+fn seek(file: &File, offset: u64, whence: i32) -> Result<Option<u64>> {
+    let offset = libc::off_t::try_from(offset).map_err(io::Error::other).or_io_error("invalid file offset")?;
+    let result = unsafe { libc::lseek(file.as_raw_fd(), offset, whence) };
+    if result >= 0 {
+        return Ok(u64::try_from(result).ok());
+    }
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::ENXIO) { Ok(None) } else { Err(err).or_io_error("failed to seek file") }
+}
+
+#[cfg(target_os = "macos")]
+fn punch_hole(file: &File, offset: u64, length: u64) -> Result<()> {
+    let args = libc::fpunchhole_t {
+        fp_flags: 0,
+        reserved: 0,
+        fp_offset: libc::off_t::try_from(offset).map_err(io::Error::other).or_io_error("invalid file offset")?,
+        fp_length: libc::off_t::try_from(length).map_err(io::Error::other).or_io_error("invalid file length")?,
+    };
+    let result = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_PUNCHHOLE, &args) };
+    if result == 0 { Ok(()) } else { Err(io::Error::last_os_error()).or_io_error("failed to punch hole") }
+}
+
+#[cfg(target_os = "linux")]
+fn punch_hole(file: &File, offset: u64, length: u64) -> Result<()> {
+    let offset = libc::off_t::try_from(offset).map_err(io::Error::other).or_io_error("invalid file offset")?;
+    let length = libc::off_t::try_from(length).map_err(io::Error::other).or_io_error("invalid file length")?;
+    let result = unsafe {
+        libc::fallocate(file.as_raw_fd(), libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE, offset, length)
+    };
+    if result == 0 { Ok(()) } else { Err(io::Error::last_os_error()).or_io_error("failed to punch hole") }
+}
+
+#[cfg(target_os = "macos")]
+fn clone_file(from: &Path, to: &Path) -> bool {
+    use std::ffi::CString;
+
+    let (Ok(from), Ok(to)) =
+        (CString::new(from.to_string_lossy().as_bytes()), CString::new(to.to_string_lossy().as_bytes()))
+    else {
+        return false;
+    };
+    unsafe { libc::clonefile(from.as_ptr(), to.as_ptr(), 0) == 0 }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn clone_file(_from: &Path, _to: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::os::unix::fs::MetadataExt as _;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn copy_sparse_file() {
+        let dir = env::temp_dir().join(Uuid::now_v7().to_string());
+        fs::create_dir_all(&dir).unwrap();
+        let from = dir.join("from.img");
+        let file = File::create(&from).unwrap();
+        file.set_len(64 * 1024 * 1024).unwrap();
+        file.write_all_at(b"head", 0).unwrap();
+        file.write_all_at(b"tail", 32 * 1024 * 1024).unwrap();
+
+        let to = dir.join("to.img");
+        copy(&from, &to).unwrap();
+        assert_eq!(fs::read(&from).unwrap(), fs::read(&to).unwrap());
+        let data_size: u64 =
+            data_ranges(&File::open(&to).unwrap()).unwrap().iter().map(|(start, end)| end - start).sum();
+        assert!(data_size > 0 && data_size < 8 * 1024 * 1024, "holes are kept, data_size={data_size}");
+        let allocated = fs::metadata(&to).unwrap().blocks() * 512;
+        assert!(allocated < 8 * 1024 * 1024, "holes are not allocated, allocated={allocated}");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn punch_zero_blocks() {
+        let dir = env::temp_dir().join(Uuid::now_v7().to_string());
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("disk.img");
+        let file = File::create(&path).unwrap();
+        let mut data = vec![0; 4 * 1024 * 1024];
+        data[0] = 1;
+        file.write_all_at(&data, 0).unwrap();
+        file.sync_all().unwrap();
+
+        let punched = super::punch_zero_blocks(&path).unwrap();
+        assert_eq!(4 * 1024 * 1024 - PUNCH_BLOCK_SIZE, punched);
+        assert_eq!(data, fs::read(&path).unwrap());
+        assert_eq!(vec![(0, PUNCH_BLOCK_SIZE)], data_ranges(&File::open(&path).unwrap()).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,23 @@
+use std::cmp::max;
+
+// print rows aligned by the widest cell of each column
+pub fn print_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) {
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = max(*width, cell.chars().count());
+        }
+    }
+    print_row(&header, &widths);
+    for row in rows {
+        print_row(row, &widths);
+    }
+}
+
+fn print_row<T>(row: &[T], widths: &[usize])
+where
+    T: AsRef<str>,
+{
+    let cells: Vec<String> = row.iter().zip(widths).map(|(cell, width)| format!("{:<width$}", cell.as_ref())).collect();
+    println!("{}", cells.join("  ").trim_end());
+}
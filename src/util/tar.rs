@@ -0,0 +1,504 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+use std::os::unix::fs::FileExt as _;
+use std::path::Path;
+
+use super::sha256::Sha256;
+use super::sparse_file;
+use super::time;
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::bytes::put;
+
+// pax format (posix.1-2001), sparse file is stored as GNU sparse 1.0, which both GNU tar and bsdtar can extract
+const BLOCK_SIZE: usize = 512;
+const BUFFER_SIZE: usize = 1024 * 1024;
+// max size fits in 11 octal digits
+const MAX_OCTAL_SIZE: u64 = 0o77_777_777_777;
+
+pub struct TarWriter<W>
+where
+    W: Write,
+{
+    writer: W,
+}
+
+impl<W> TarWriter<W>
+where
+    W: Write,
+{
+    pub fn new(writer: W) -> Self {
+        TarWriter { writer }
+    }
+
+    // return sha256 of stored content
+    pub fn append_data(&mut self, name: &str, data: &[u8]) -> Result<String> {
+        self.write_entry_header(name, data.len() as u64, &[])?;
+        let mut sha256 = Sha256::new();
+        sha256.update(data);
+        self.write(data)?;
+        self.write_padding(data.len() as u64)?;
+        Ok(sha256.finish())
+    }
+
+    // return sha256 of stored content, which is sparse map and data regions for sparse file
+    pub fn append_sparse_file(&mut self, name: &str, path: &Path) -> Result<String> {
+        let file = File::open(path).or_io_error("failed to open file")?;
+        let real_size = file.metadata().or_io_error("failed to read file metadata")?.len();
+        let mut ranges = sparse_file::data_ranges(&file)?;
+        // end with empty region, so extracted file has real size even if it ends with hole
+        if ranges.last().is_none_or(|(_, end)| *end < real_size) {
+            ranges.push((real_size, real_size));
+        }
+
+        let regions: Vec<String> = ranges.iter().map(|(start, end)| format!("{start}\n{}\n", end - start)).collect();
+        let mut sparse_map = format!("{}\n{}", ranges.len(), regions.concat()).into_bytes();
+        sparse_map.resize(sparse_map.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE, 0);
+        let data_size: u64 = ranges.iter().map(|(start, end)| end - start).sum();
+        let stored_size = sparse_map.len() as u64 + data_size;
+
+        let records = [
+            ("GNU.sparse.major", "1".to_owned()),
+            ("GNU.sparse.minor", "0".to_owned()),
+            ("GNU.sparse.name", name.to_owned()),
+            ("GNU.sparse.realsize", real_size.to_string()),
+        ];
+        self.write_entry_header(&format!("GNUSparseFile.0/{name}"), stored_size, &records)?;
+
+        let mut sha256 = Sha256::new();
+        sha256.update(&sparse_map);
+        self.write(&sparse_map)?;
+        let mut buffer = vec![];
+        for (start, end) in ranges {
+            let mut offset = start;
+            while offset < end {
+                let size = usize::try_from(end - offset).map_or(BUFFER_SIZE, |size| size.min(BUFFER_SIZE));
+                buffer.resize(size, 0);
+                file.read_exact_at(&mut buffer, offset).or_io_error("failed to read file")?;
+                sha256.update(&buffer);
+                self.write(&buffer)?;
+                offset += size as u64;
+            }
+        }
+        self.write_padding(stored_size)?;
+        Ok(sha256.finish())
+    }
+
+    // archive ends with two empty blocks
+    pub fn finish(mut self) -> Result<W> {
+        self.write(&[0; BLOCK_SIZE * 2])?;
+        self.writer.flush().or_io_error("failed to flush archive")?;
+        Ok(self.writer)
+    }
+
+    fn write_entry_header(&mut self, name: &str, size: u64, records: &[(&str, String)]) -> Result<()> {
+        let mut records = records.to_vec();
+        if name.len() > 100 {
+            records.push(("path", name.to_owned()));
+        }
+        if size > MAX_OCTAL_SIZE {
+            records.push(("size", size.to_string()));
+        }
+        if !records.is_empty() {
+            let mut pax = String::new();
+            for (key, value) in records {
+                pax.push_str(&pax_record(key, &value));
+            }
+            self.write(&header(&format!("PaxHeaders.0/{name}"), pax.len() as u64, b'x'))?;
+            self.write(pax.as_bytes())?;
+            self.write_padding(pax.len() as u64)?;
+        }
+        self.write(&header(name, size, b'0'))
+    }
+
+    fn write_padding(&mut self, size: u64) -> Result<()> {
+        let padding = padding(size);
+        self.write([0; BLOCK_SIZE].get(..padding).unwrap_or_default())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.writer.write_all(data).or_io_error("failed to write archive")
+    }
+}
+
+pub struct TarReader<R>
+where
+    R: Read,
+{
+    reader: R,
+}
+
+pub struct Entry {
+    pub name: String,
+    // real size of file
+    pub size: u64,
+    // sha256 of stored content
+    pub sha256: String,
+}
+
+impl<R> TarReader<R>
+where
+    R: Read,
+{
+    pub fn new(reader: R) -> Self {
+        TarReader { reader }
+    }
+
+    // extract regular files into dir, entry name must be plain file name, return extracted entries
+    pub fn extract(&mut self, dir: &Path) -> Result<Vec<Entry>> {
+        let mut entries = vec![];
+        let mut pax: HashMap<String, String> = HashMap::new();
+        loop {
+            let mut header = [0; BLOCK_SIZE];
+            self.read(&mut header)?;
+            if header.iter().all(|byte| *byte == 0) {
+                return Ok(entries);
+            }
+            validate_checksum(&header)?;
+
+            let mut size = parse_number(field(&header, 124, 12))?;
+            if let Some(pax_size) = pax.get("size") {
+                size = parse_decimal(pax_size)?;
+            }
+            match field(&header, 156, 1) {
+                b"x" => {
+                    pax = parse_pax(&self.read_data(size)?)?;
+                    continue;
+                }
+                b"g" => {
+                    self.read_data(size)?;
+                }
+                b"0" | b"\0" => entries.push(self.extract_file(&header, size, &pax, dir)?),
+                b"5" => {}
+                type_flag => {
+                    return Err(invalid_archive(&format!(
+                        "unsupported entry type, type={}",
+                        String::from_utf8_lossy(type_flag)
+                    )));
+                }
+            }
+            pax.clear();
+        }
+    }
+
+    fn extract_file(
+        &mut self,
+        header: &[u8; BLOCK_SIZE],
+        size: u64,
+        pax: &HashMap<String, String>,
+        dir: &Path,
+    ) -> Result<Entry> {
+        let sparse = pax.get("GNU.sparse.major").is_some_and(|major| major == "1");
+        let name = match (pax.get("GNU.sparse.name"), pax.get("path")) {
+            (Some(name), _) if sparse => name.clone(),
+            (_, Some(path)) => path.clone(),
+            _ => text(field(header, 0, 100)),
+        };
+        if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+            return Err(invalid_archive(&format!("invalid entry name, name={name}")));
+        }
+
+        let file = File::create_new(dir.join(&name)).or_io_error("failed to create file")?;
+        let mut sha256 = Sha256::new();
+        let real_size = if sparse {
+            let real_size = parse_decimal(pax.get("GNU.sparse.realsize").map_or("", String::as_str))?;
+            file.set_len(real_size).or_io_error("failed to resize file")?;
+            let (ranges, map_size) = self.read_sparse_map(&mut sha256)?;
+            let stored_size =
+                ranges.iter().try_fold(map_size, |stored_size, (_, length)| stored_size.checked_add(*length));
+            if stored_size != Some(size) {
+                return Err(invalid_archive(&format!("sparse map does not match entry size, name={name}")));
+            }
+            if ranges.iter().any(|(offset, length)| offset.checked_add(*length).is_none_or(|end| end > real_size)) {
+                return Err(invalid_archive(&format!("sparse region exceeds real size, name={name}")));
+            }
+            for (offset, length) in ranges {
+                self.copy_data(length, &mut sha256, |data, position| {
+                    file.write_all_at(data, offset + position).or_io_error("failed to write file")
+                })?;
+            }
+            real_size
+        } else {
+            self.copy_data(size, &mut sha256, |data, position| {
+                file.write_all_at(data, position).or_io_error("failed to write file")
+            })?;
+            size
+        };
+        self.skip_padding(size)?;
+        file.sync_all().or_io_error("failed to sync file")?;
+        Ok(Entry { name, size: real_size, sha256: sha256.finish() })
+    }
+
+    // return [(offset, length)] and size of map blocks
+    fn read_sparse_map(&mut self, sha256: &mut Sha256) -> Result<(Vec<(u64, u64)>, u64)> {
+        let mut text = String::new();
+        let mut map_size = 0;
+        let mut numbers: Vec<u64> = vec![];
+        loop {
+            let mut block = [0; BLOCK_SIZE];
+            self.read(&mut block)?;
+            sha256.update(&block);
+            map_size += BLOCK_SIZE as u64;
+            text.push_str(&String::from_utf8_lossy(&block));
+
+            // numbers are separated by newline, last line in block may continue in next block
+            let complete = text.rfind('\n').map_or("", |index| text.split_at(index).0);
+            numbers = complete.split('\n').map(parse_decimal).collect::<Result<_>>()?;
+            let count = numbers.first().copied().unwrap_or(0);
+            let count = count.checked_mul(2).ok_or_else(|| invalid_archive("sparse map count overflows"))?;
+            if numbers.len() as u64 > count {
+                break;
+            }
+        }
+        let ranges =
+            numbers.iter().skip(1).step_by(2).copied().zip(numbers.iter().skip(2).step_by(2).copied()).collect();
+        Ok((ranges, map_size))
+    }
+
+    fn copy_data<F>(&mut self, size: u64, sha256: &mut Sha256, mut write: F) -> Result<()>
+    where
+        F: FnMut(&[u8], u64) -> Result<()>,
+    {
+        let mut buffer = vec![];
+        let mut position = 0;
+        while position < size {
+            let length = usize::try_from(size - position).map_or(BUFFER_SIZE, |length| length.min(BUFFER_SIZE));
+            buffer.resize(length, 0);
+            self.read(&mut buffer)?;
+            sha256.update(&buffer);
+            write(&buffer, position)?;
+            position += length as u64;
+        }
+        Ok(())
+    }
+
+    fn read_data(&mut self, size: u64) -> Result<Vec<u8>> {
+        let mut data = vec![];
+        let mut sha256 = Sha256::new();
+        self.copy_data(size, &mut sha256, |buffer, _| {
+            data.extend_from_slice(buffer);
+            Ok(())
+        })?;
+        self.skip_padding(size)?;
+        Ok(data)
+    }
+
+    fn skip_padding(&mut self, size: u64) -> Result<()> {
+        let mut padding = [0; BLOCK_SIZE];
+        self.read(padding.get_mut(..self::padding(size)).unwrap_or_default())
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<()> {
+        self.reader.read_exact(buffer).or_io_error("failed to read archive")
+    }
+}
+
+fn header(name: &str, size: u64, type_flag: u8) -> [u8; BLOCK_SIZE] {
+    let mut header = [0; BLOCK_SIZE];
+    let name = name.as_bytes();
+    put(&mut header, 0, name.get(..100).unwrap_or(name));
+    put(&mut header, 100, format!("{:07o}\0", 0o644).as_bytes());
+    put(&mut header, 108, format!("{:07o}\0", 0).as_bytes());
+    put(&mut header, 116, format!("{:07o}\0", 0).as_bytes());
+    put(&mut header, 124, format!("{:011o}\0", if size > MAX_OCTAL_SIZE { 0 } else { size }).as_bytes());
+    put(&mut header, 136, format!("{:011o}\0", time::now().min(MAX_OCTAL_SIZE)).as_bytes());
+    put(&mut header, 156, &[type_flag]);
+    put(&mut header, 257, b"ustar\0");
+    put(&mut header, 263, b"00");
+    let checksum = checksum(&header);
+    put(&mut header, 148, format!("{checksum:06o}\0 ").as_bytes());
+    header
+}
+
+fn field(header: &[u8; BLOCK_SIZE], offset: usize, length: usize) -> &[u8] {
+    header.get(offset..offset + length).unwrap_or_default()
+}
+
+// checksum is calculated with checksum field as spaces
+fn checksum(header: &[u8; BLOCK_SIZE]) -> u64 {
+    header
+        .iter()
+        .enumerate()
+        .map(|(index, byte)| if (148..156).contains(&index) { u64::from(b' ') } else { u64::from(*byte) })
+        .sum()
+}
+
+fn validate_checksum(header: &[u8; BLOCK_SIZE]) -> Result<()> {
+    let expected = parse_number(field(header, 148, 8))?;
+    if checksum(header) == expected { Ok(()) } else { Err(invalid_archive("header checksum mismatch")) }
+}
+
+// octal, or base-256 if high bit of first byte is set (GNU extension for large numbers)
+fn parse_number(value: &[u8]) -> Result<u64> {
+    if let Some((first, rest)) = value.split_first()
+        && first & 0x80 != 0
+    {
+        return Ok(rest.iter().fold(u64::from(first & 0x7f), |number, byte| (number << 8) | u64::from(*byte)));
+    }
+    let value = text(value);
+    let value = value.trim_matches(' ');
+    if value.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(value, 8).map_err(|err| invalid_archive(&format!("invalid number, value={value}, err={err}")))
+}
+
+fn parse_decimal(value: &str) -> Result<u64> {
+    value.parse().map_err(|err| invalid_archive(&format!("invalid number, value={value}, err={err}")))
+}
+
+// record is "[length] [key]=[value]\n", length includes itself
+fn pax_record(key: &str, value: &str) -> String {
+    let body = format!(" {key}={value}\n");
+    let mut length = body.len() + 1;
+    while length.to_string().len() + body.len() != length {
+        length += 1;
+    }
+    format!("{length}{body}")
+}
+
+fn parse_pax(data: &[u8]) -> Result<HashMap<String, String>> {
+    let mut records = HashMap::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let length = rest
+            .iter()
+            .position(|byte| *byte == b' ')
+            .and_then(|index| rest.get(..index))
+            .map(text)
+            .ok_or_else(|| invalid_archive("invalid pax record"))?;
+        let length = usize::try_from(parse_decimal(&length)?).map_err(|err| invalid_archive(&err.to_string()))?;
+        let (record, next) =
+            rest.split_at_checked(length).ok_or_else(|| invalid_archive("pax record exceeds header size"))?;
+        let record = String::from_utf8_lossy(record);
+        let (_, key_value) = record.split_once(' ').ok_or_else(|| invalid_archive("invalid pax record"))?;
+        let (key, value) = key_value
+            .strip_suffix('\n')
+            .and_then(|key_value| key_value.split_once('='))
+            .ok_or_else(|| invalid_archive("invalid pax record"))?;
+        records.insert(key.to_owned(), value.to_owned());
+        rest = next;
+    }
+    Ok(records)
+}
+
+// nul terminated string
+fn text(value: &[u8]) -> String {
+    let end = value.iter().position(|byte| *byte == 0).unwrap_or(value.len());
+    String::from_utf8_lossy(value.get(..end).unwrap_or_default()).to_string()
+}
+
+fn padding(size: u64) -> usize {
+    let remainder = (size % BLOCK_SIZE as u64) as usize;
+    if remainder == 0 { 0 } else { BLOCK_SIZE - remainder }
+}
+
+fn invalid_archive(message: &str) -> VzError {
+    VzError::InvalidArgument(format!("invalid archive, {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn pax_records() {
+        assert_eq!("28 GNU.sparse.name=disk.img\n", pax_record("GNU.sparse.name", "disk.img"));
+        assert_eq!("8 a=bcd\n", pax_record("a", "bcd"));
+        let records = parse_pax(b"28 GNU.sparse.name=disk.img\n8 a=bcd\n").unwrap();
+        assert_eq!(Some("disk.img"), records.get("GNU.sparse.name").map(String::as_str));
+        assert_eq!(Some("bcd"), records.get("a").map(String::as_str));
+    }
+
+    #[test]
+    fn write_and_extract() {
+        let dir = env::temp_dir().join(Uuid::now_v7().to_string());
+        fs::create_dir_all(&dir).unwrap();
+        let disk_path = dir.join("source.img");
+        let file = File::create(&disk_path).unwrap();
+        file.set_len(16 * 1024 * 1024).unwrap();
+        file.write_all_at(b"head", 0).unwrap();
+        file.write_all_at(b"middle", 8 * 1024 * 1024).unwrap();
+
+        let mut writer = TarWriter::new(vec![]);
+        let config_sha256 = writer.append_data("config.json", b"{}").unwrap();
+        let disk_sha256 = writer.append_sparse_file("disk.img", &disk_path).unwrap();
+        let archive = writer.finish().unwrap();
+
+        let target_dir = dir.join("target");
+        fs::create_dir_all(&target_dir).unwrap();
+        let entries = TarReader::new(archive.as_slice()).extract(&target_dir).unwrap();
+        let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(vec!["config.json", "disk.img"], names);
+        let sha256: Vec<&str> = entries.iter().map(|entry| entry.sha256.as_str()).collect();
+        assert_eq!(vec![config_sha256.as_str(), disk_sha256.as_str()], sha256);
+        assert_eq!("{}", fs::read_to_string(target_dir.join("config.json")).unwrap());
+        assert_eq!(fs::read(&disk_path).unwrap(), fs::read(target_dir.join("disk.img")).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn extract_invalid_name() {
+        let mut writer = TarWriter::new(vec![]);
+        writer.append_data("../config.json", b"{}").unwrap();
+        let archive = writer.finish().unwrap();
+        let result = TarReader::new(archive.as_slice()).extract(&env::temp_dir());
+        assert!(matches!(result, Err(VzError::InvalidArgument(_))), "entry name must be plain file name");
+    }
+
+    // sparse disk.img entry with given sparse map and data, stored size is taken from sparse map blocks + data
+    fn sparse_archive(sparse_map: &[u8], real_size: u64, data: &[u8]) -> Vec<u8> {
+        let mut sparse_map = sparse_map.to_vec();
+        sparse_map.resize(sparse_map.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE, 0);
+        let records = [
+            ("GNU.sparse.major", "1".to_owned()),
+            ("GNU.sparse.minor", "0".to_owned()),
+            ("GNU.sparse.name", "disk.img".to_owned()),
+            ("GNU.sparse.realsize", real_size.to_string()),
+        ];
+        let mut writer = TarWriter::new(vec![]);
+        let stored_size = (sparse_map.len() + data.len()) as u64;
+        writer.write_entry_header("GNUSparseFile.0/disk.img", stored_size, &records).unwrap();
+        writer.write(&sparse_map).unwrap();
+        writer.write(data).unwrap();
+        writer.write_padding(stored_size).unwrap();
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn extract_sparse_region_out_of_bounds() {
+        let dir = env::temp_dir().join(Uuid::now_v7().to_string());
+        fs::create_dir_all(&dir).unwrap();
+        let archive = sparse_archive(b"1\n100\n4\n", 10, b"data");
+
+        let result = TarReader::new(archive.as_slice()).extract(&dir);
+        assert!(matches!(result, Err(VzError::InvalidArgument(_))), "region must be within real size");
+        assert_eq!(10, fs::metadata(dir.join("disk.img")).unwrap().len(), "file is not extended");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn extract_sparse_map_overflow() {
+        // count * 2 overflows, sum of lengths overflows
+        for sparse_map in [format!("{}\n0\n4\n", u64::MAX), format!("2\n0\n4\n4\n{}\n", u64::MAX - 1)] {
+            let dir = env::temp_dir().join(Uuid::now_v7().to_string());
+            fs::create_dir_all(&dir).unwrap();
+            let archive = sparse_archive(sparse_map.as_bytes(), 10, b"data");
+            let result = TarReader::new(archive.as_slice()).extract(&dir);
+            assert!(
+                matches!(result, Err(VzError::InvalidArgument(_))),
+                "sparse map must not overflow, map={sparse_map}"
+            );
+            fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+}
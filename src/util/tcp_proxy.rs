@@ -0,0 +1,189 @@
+use std::io;
+use std::net::Shutdown;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use tracing::info;
+use tracing::warn;
+
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// resolve target address per connection, invalidate is called when connecting to resolved address failed,
+// e.g. to drop cached address
+pub trait Target: Send + Sync + 'static {
+    fn resolve(&self) -> Option<SocketAddr>;
+
+    fn invalidate(&self) {}
+}
+
+impl<F> Target for F
+where
+    F: Fn() -> Option<SocketAddr> + Send + Sync + 'static,
+{
+    fn resolve(&self) -> Option<SocketAddr> {
+        self()
+    }
+}
+
+// listen on address, and proxy every connection to target, return bound address
+pub fn start<T: Target>(address: SocketAddr, target: T) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(address).or_io_error(&format!("failed to listen, address={address}"))?;
+    let local_address = listener.local_addr().or_io_error("failed to get listen address")?;
+    let target = Arc::new(target);
+    thread::spawn(move || {
+        for client in listener.incoming() {
+            match client {
+                Ok(client) => {
+                    let target = Arc::clone(&target);
+                    thread::spawn(move || handle(client, target.as_ref()));
+                }
+                Err(err) => warn!("failed to accept connection, address={local_address}, err={err}"),
+            }
+        }
+    });
+    Ok(local_address)
+}
+
+// client is closed if target is unknown or unreachable
+fn handle<T: Target>(client: TcpStream, target: &T) {
+    let Some((address, server)) = connect(target) else {
+        return;
+    };
+    if let Err(err) = proxy(client, server) {
+        info!("proxy connection closed, target={address}, err={err}");
+    }
+}
+
+// resolved address may be stale, retry once with address resolved again
+fn connect<T: Target>(target: &T) -> Option<(SocketAddr, TcpStream)> {
+    let Some(address) = target.resolve() else {
+        warn!("proxy target is unknown, close connection");
+        return None;
+    };
+    let err = match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+        Ok(server) => return Some((address, server)),
+        Err(err) => err,
+    };
+    target.invalidate();
+    match target.resolve() {
+        Some(retry_address) if retry_address != address => {
+            match TcpStream::connect_timeout(&retry_address, CONNECT_TIMEOUT) {
+                Ok(server) => Some((retry_address, server)),
+                Err(retry_err) => {
+                    info!("failed to connect proxy target, target={retry_address}, err={retry_err}");
+                    None
+                }
+            }
+        }
+        _ => {
+            info!("failed to connect proxy target, target={address}, err={err}");
+            None
+        }
+    }
+}
+
+fn proxy(client: TcpStream, server: TcpStream) -> io::Result<()> {
+    let mut client_reader = client.try_clone()?;
+    let mut server_writer = server.try_clone()?;
+    // half close, so each direction finishes independently
+    let upstream = thread::spawn(move || {
+        let result = io::copy(&mut client_reader, &mut server_writer);
+        server_writer.shutdown(Shutdown::Write).and(result)
+    });
+    let (mut server_reader, mut client_writer) = (server, client);
+    let downstream = io::copy(&mut server_reader, &mut client_writer);
+    client_writer.shutdown(Shutdown::Write)?;
+    upstream.join().map_err(|_err| io::Error::other("proxy thread panicked"))??;
+    downstream.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read as _;
+    use std::io::Write as _;
+    use std::net::Ipv4Addr;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+
+    fn local() -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::LOCALHOST, 0))
+    }
+
+    // reply upper case of request
+    fn start_upper_case_server() -> SocketAddr {
+        let listener = TcpListener::bind(local()).unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = vec![];
+                stream.read_to_end(&mut request).unwrap();
+                stream.write_all(&request.to_ascii_uppercase()).unwrap();
+            }
+        });
+        address
+    }
+
+    #[test]
+    fn proxy_to_target() {
+        let server = start_upper_case_server();
+        let proxy = start(local(), move || Some(server)).unwrap();
+
+        let mut client = TcpStream::connect(proxy).unwrap();
+        client.write_all(b"hello").unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert_eq!("HELLO", response);
+    }
+
+    #[test]
+    fn close_if_target_unknown() {
+        let proxy = start(local(), || None).unwrap();
+
+        let mut client = TcpStream::connect(proxy).unwrap();
+        let mut response = vec![];
+        client.read_to_end(&mut response).unwrap();
+        assert!(response.is_empty(), "connection should be closed");
+    }
+
+    struct StaleTarget {
+        stale: SocketAddr,
+        current: SocketAddr,
+        invalidated: AtomicBool,
+    }
+
+    impl Target for StaleTarget {
+        fn resolve(&self) -> Option<SocketAddr> {
+            Some(if self.invalidated.load(Ordering::SeqCst) { self.current } else { self.stale })
+        }
+
+        fn invalidate(&self) {
+            self.invalidated.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn resolve_again_if_connect_failed() {
+        // bind then drop to get a local port nobody listens on
+        let stale = TcpListener::bind(local()).unwrap().local_addr().unwrap();
+        let current = start_upper_case_server();
+        let proxy = start(local(), StaleTarget { stale, current, invalidated: AtomicBool::new(false) }).unwrap();
+
+        let mut client = TcpStream::connect(proxy).unwrap();
+        client.write_all(b"hello").unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert_eq!("HELLO", response);
+    }
+}
@@ -0,0 +1,41 @@
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+// seconds since unix epoch
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs())
+}
+
+// e.g. 2024-06-01T08:30:00Z
+pub fn format_utc(seconds: u64) -> String {
+    let [year, month, day, hour, minute, second] = utc(seconds);
+    format!("{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}Z")
+}
+
+// return [year, month, day, hour, minute, second], refer to http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+pub fn utc(seconds: u64) -> [u64; 6] {
+    let days = seconds / 86400;
+    let time = seconds % 86400;
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    [year, month, day, time / 3600, time % 3600 / 60, time % 60]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format() {
+        assert_eq!("1970-01-01T00:00:00Z", format_utc(0));
+        assert_eq!("2000-02-29T12:34:56Z", format_utc(951_827_696));
+        assert_eq!("2024-12-31T23:59:59Z", format_utc(1_735_689_599));
+    }
+}
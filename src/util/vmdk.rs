@@ -0,0 +1,194 @@
+use std::fs::File;
+use std::os::unix::fs::FileExt as _;
+
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::bytes::le_u16;
+use crate::util::bytes::le_u32;
+use crate::util::bytes::le_u64;
+use crate::util::inflate;
+
+// https://www.vmware.com/app/vmdk/?src=vmdk, hosted sparse extent, e.g. monolithicSparse or streamOptimized
+pub const MAGIC: &[u8] = b"KDMV";
+const SECTOR_SIZE: u64 = 512;
+const FLAG_COMPRESSED: u32 = 1 << 16;
+// grain directory is written in footer, used by streamOptimized
+const GD_AT_END: u64 = u64::MAX;
+const COMPRESS_DEFLATE: u16 = 1;
+
+struct Header {
+    flags: u32,
+    capacity: u64,
+    grain_size: u64,
+    gtes_per_gt: u32,
+    gd_offset: u64,
+    compress_algorithm: u16,
+}
+
+// return virtual disk size
+pub fn virtual_size(file: &File) -> Result<u64> {
+    Ok(read_header(file)?.capacity * SECTOR_SIZE)
+}
+
+// convert sparse extent to sparse raw image, unallocated grains become holes
+pub fn to_raw(from: &File, to: &File) -> Result<()> {
+    let header = read_header(from)?;
+    let size = header.capacity * SECTOR_SIZE;
+    let grain_bytes = header.grain_size * SECTOR_SIZE;
+    to.set_len(size).or_io_error("failed to resize file")?;
+
+    let gt_count = header.capacity.div_ceil(header.grain_size).div_ceil(u64::from(header.gtes_per_gt));
+    let mut directory = vec![0; (gt_count * 4) as usize];
+    from.read_exact_at(&mut directory, header.gd_offset * SECTOR_SIZE)
+        .or_io_error("failed to read vmdk grain directory")?;
+    let mut table = vec![0; header.gtes_per_gt as usize * 4];
+    let mut grain = vec![];
+    let mut guest_offset = 0;
+    for gt_offset in directory.chunks_exact(4).map(|bytes| u64::from(le_u32(bytes, 0))) {
+        if gt_offset == 0 {
+            guest_offset += grain_bytes * u64::from(header.gtes_per_gt);
+            continue;
+        }
+        from.read_exact_at(&mut table, gt_offset * SECTOR_SIZE).or_io_error("failed to read vmdk grain table")?;
+        for grain_offset in table.chunks_exact(4).map(|bytes| u64::from(le_u32(bytes, 0))) {
+            if guest_offset >= size {
+                break;
+            }
+            let length = (size - guest_offset).min(grain_bytes) as usize;
+            // 0 is unallocated, 1 is zero grain
+            if grain_offset > 1 {
+                if header.flags & FLAG_COMPRESSED == 0 {
+                    grain.resize(length, 0);
+                    from.read_exact_at(&mut grain, grain_offset * SECTOR_SIZE)
+                        .or_io_error("failed to read vmdk grain")?;
+                } else {
+                    grain = read_compressed_grain(from, grain_offset, grain_bytes)?;
+                    grain.resize(length, 0);
+                }
+                if grain.iter().any(|byte| *byte != 0) {
+                    to.write_all_at(&grain, guest_offset).or_io_error("failed to write file")?;
+                }
+            }
+            guest_offset += grain_bytes;
+        }
+    }
+    to.sync_all().or_io_error("failed to sync file")
+}
+
+// compressed grain starts with lba (u64) and compressed size (u32), followed by zlib stream
+fn read_compressed_grain(file: &File, grain_offset: u64, grain_bytes: u64) -> Result<Vec<u8>> {
+    let mut marker = [0; 12];
+    file.read_exact_at(&mut marker, grain_offset * SECTOR_SIZE).or_io_error("failed to read vmdk grain marker")?;
+    let mut data = vec![0; le_u32(&marker, 8) as usize];
+    file.read_exact_at(&mut data, grain_offset * SECTOR_SIZE + 12).or_io_error("failed to read vmdk grain")?;
+    // skip 2 bytes zlib header, adler32 checksum after deflate stream is ignored
+    let deflate = data.get(2..).ok_or_else(|| invalid_vmdk("invalid compressed grain"))?;
+    inflate::inflate(deflate, grain_bytes as usize)
+}
+
+fn read_header(file: &File) -> Result<Header> {
+    let mut block = [0; SECTOR_SIZE as usize];
+    file.read_exact_at(&mut block, 0).or_io_error("failed to read vmdk header")?;
+    if block.get(..4) != Some(MAGIC) {
+        return Err(invalid_vmdk("only sparse extent is supported"));
+    }
+    let mut header = parse_header(&block);
+    if header.gd_offset == GD_AT_END {
+        // footer is followed by end of stream marker
+        let len = file.metadata().or_io_error("failed to read file metadata")?.len();
+        let offset = len.checked_sub(SECTOR_SIZE * 2).ok_or_else(|| invalid_vmdk("footer not found"))?;
+        file.read_exact_at(&mut block, offset).or_io_error("failed to read vmdk footer")?;
+        if block.get(..4) != Some(MAGIC) {
+            return Err(invalid_vmdk("footer not found"));
+        }
+        header = parse_header(&block);
+    }
+    if header.grain_size == 0 || header.gtes_per_gt == 0 || header.gd_offset == GD_AT_END {
+        return Err(invalid_vmdk("invalid header"));
+    }
+    if header.flags & FLAG_COMPRESSED != 0 && header.compress_algorithm != COMPRESS_DEFLATE {
+        return Err(invalid_vmdk(&format!(
+            "unsupported compress algorithm, compress_algorithm={}",
+            header.compress_algorithm
+        )));
+    }
+    Ok(header)
+}
+
+fn parse_header(block: &[u8]) -> Header {
+    Header {
+        flags: le_u32(block, 8),
+        capacity: le_u64(block, 12),
+        grain_size: le_u64(block, 20),
+        gtes_per_gt: le_u32(block, 44),
+        gd_offset: le_u64(block, 56),
+        compress_algorithm: le_u16(block, 77),
+    }
+}
+
+fn invalid_vmdk(message: &str) -> VzError {
+    VzError::InvalidArgument(format!("invalid vmdk image, {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::util::bytes::le_bytes;
+    use crate::util::bytes::put;
+
+    // 1MiB disk with 64KiB grains, grain 0 is compressed "hello hello hello hello", grain 3 is zero grain
+    fn create_stream_optimized(path: &Path) -> File {
+        let file = File::options().read(true).write(true).create(true).truncate(true).open(path).unwrap();
+        let mut header = vec![0; 512];
+        put(&mut header, 0, MAGIC);
+        put(&mut header, 4, &le_bytes(3, 4));
+        put(&mut header, 8, &le_bytes(u64::from(FLAG_COMPRESSED | (1 << 17) | 1), 4));
+        put(&mut header, 12, &le_bytes(2048, 8));
+        put(&mut header, 20, &le_bytes(128, 8));
+        put(&mut header, 44, &le_bytes(512, 4));
+        put(&mut header, 77, &le_bytes(u64::from(COMPRESS_DEFLATE), 2));
+        let mut footer = header.clone();
+        put(&mut header, 56, &le_bytes(GD_AT_END, 8));
+        put(&mut footer, 56, &le_bytes(1, 8));
+        file.write_all_at(&header, 0).unwrap();
+
+        // grain directory at sector 1, grain table at sector 2-5, grain at sector 6
+        // zlib header + deflate + adler32
+        let grain = [0x78, 0xda, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x27, 0x01, 0x00, 0x00, 0x00, 0x00];
+        let mut marker = le_bytes(0, 8);
+        marker.extend(le_bytes(grain.len() as u64, 4));
+        marker.extend(grain);
+        file.write_all_at(&marker, 6 * 512).unwrap();
+        let mut table = vec![0; 2048];
+        put(&mut table, 0, &le_bytes(6, 4));
+        put(&mut table, 12, &le_bytes(1, 4));
+        file.write_all_at(&table, 2 * 512).unwrap();
+        file.write_all_at(&le_bytes(2, 4), 512).unwrap();
+        file.write_all_at(&footer, 7 * 512).unwrap();
+        file.write_all_at(&[0; 512], 8 * 512).unwrap();
+        file
+    }
+
+    #[test]
+    fn convert_stream_optimized() {
+        let dir = env::temp_dir().join(Uuid::now_v7().to_string());
+        fs::create_dir_all(&dir).unwrap();
+        let vmdk = create_stream_optimized(&dir.join("disk.vmdk"));
+        assert_eq!(1024 * 1024, virtual_size(&vmdk).unwrap());
+
+        let raw = File::create(dir.join("disk.img")).unwrap();
+        to_raw(&vmdk, &raw).unwrap();
+        let mut expected = b"hello hello hello hello".to_vec();
+        expected.resize(1024 * 1024, 0);
+        assert_eq!(expected, fs::read(dir.join("disk.img")).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
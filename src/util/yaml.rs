@@ -0,0 +1,131 @@
+use serde::Serialize;
+use serde_json::Map;
+use serde_json::Value;
+
+// minimal yaml emitter for cli output, supports what serde_json::Value can represent
+pub fn to_yaml<T>(object: &T) -> String
+where
+    T: Serialize,
+{
+    let value = serde_json::to_value(object).unwrap_or_else(|err| panic!("failed to serialize, err={err}"));
+    let mut yaml = String::new();
+    match &value {
+        Value::Array(values) if !values.is_empty() => write_array(&mut yaml, values, 0),
+        Value::Object(map) if !map.is_empty() => write_object(&mut yaml, map, 0),
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) | Value::Array(_) | Value::Object(_) => {
+            yaml.push_str(&scalar(&value));
+            yaml.push('\n');
+        }
+    }
+    yaml
+}
+
+fn write_array(yaml: &mut String, values: &[Value], indent: usize) {
+    for value in values {
+        yaml.push_str(&" ".repeat(indent));
+        yaml.push('-');
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                // first field is written on same line of "-"
+                let mut nested = String::new();
+                write_object(&mut nested, map, indent + 2);
+                yaml.push(' ');
+                yaml.push_str(nested.trim_start());
+            }
+            Value::Array(nested_values) if !nested_values.is_empty() => {
+                yaml.push('\n');
+                write_array(yaml, nested_values, indent + 2);
+            }
+            Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) | Value::Array(_) | Value::Object(_) => {
+                write_scalar(yaml, value);
+            }
+        }
+    }
+}
+
+fn write_object(yaml: &mut String, map: &Map<String, Value>, indent: usize) {
+    for (key, value) in map {
+        yaml.push_str(&" ".repeat(indent));
+        yaml.push_str(&string(key));
+        yaml.push(':');
+        match value {
+            Value::Object(nested_map) if !nested_map.is_empty() => {
+                yaml.push('\n');
+                write_object(yaml, nested_map, indent + 2);
+            }
+            Value::Array(values) if !values.is_empty() => {
+                yaml.push('\n');
+                write_array(yaml, values, indent + 2);
+            }
+            Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) | Value::Array(_) | Value::Object(_) => {
+                write_scalar(yaml, value);
+            }
+        }
+    }
+}
+
+fn write_scalar(yaml: &mut String, value: &Value) {
+    yaml.push(' ');
+    yaml.push_str(&scalar(value));
+    yaml.push('\n');
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_owned(),
+        Value::Bool(value) => value.to_string(),
+        Value::Number(value) => value.to_string(),
+        Value::String(value) => string(value),
+        Value::Array(_) => "[]".to_owned(),
+        Value::Object(_) => "{}".to_owned(),
+    }
+}
+
+// plain scalar only if it can't be read as other type, e.g. "true", "1.0", or "12:30" (sexagesimal in yaml 1.1)
+fn string(value: &str) -> String {
+    const RESERVED: [&str; 12] = ["true", "false", "yes", "no", "on", "off", "y", "n", "null", "~", "nan", "inf"];
+    let plain = value.starts_with(|c: char| c.is_ascii_alphabetic() || c == '/')
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/'))
+        && !RESERVED.contains(&value.to_ascii_lowercase().as_str());
+    if plain {
+        value.to_owned()
+    } else {
+        serde_json::to_string(value).unwrap_or_else(|err| panic!("failed to serialize, err={err}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn to_yaml_array_of_objects() {
+        let value = json!([
+            {"name": "debian", "pid": 123, "ip": null, "macAddress": "f6:db:b3:ec:f9:3f", "sharing": {"home": "/Users/vz"}},
+            {"name": "true", "pid": null, "ip": "192.168.64.3", "macAddress": "", "sharing": {}}
+        ]);
+        assert_eq!(
+            r#"- ip: null
+  macAddress: "f6:db:b3:ec:f9:3f"
+  name: debian
+  pid: 123
+  sharing:
+    home: /Users/vz
+- ip: "192.168.64.3"
+  macAddress: ""
+  name: "true"
+  pid: null
+  sharing: {}
+"#,
+            to_yaml(&value)
+        );
+    }
+
+    #[test]
+    fn to_yaml_empty() {
+        assert_eq!("[]\n", to_yaml(&json!([])));
+        assert_eq!("-\n  - 1\n  - 2\n", to_yaml(&json!([[1, 2]])));
+    }
+}
@@ -0,0 +1,55 @@
+use std::io;
+use std::path::Path;
+use std::process::Child;
+use std::process::Command;
+use std::process::Stdio;
+
+use crate::error::IoResultExtension as _;
+use crate::error::Result;
+use crate::error::VzError;
+
+// compression is delegated to zstd command, it's required by export / import, e.g. brew install zstd
+
+// check zstd command before creating any file, so missing zstd fails up front
+pub fn check() -> Result<()> {
+    let available = Command::new("zstd")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if available {
+        Ok(())
+    } else {
+        Err(VzError::InvalidArgument("zstd command is required, install by 'brew install zstd'".to_owned()))
+    }
+}
+
+// write stdin of returned process to compressed file
+pub fn compress(path: &Path) -> Result<Child> {
+    Command::new("zstd")
+        .args(["-q", "-T0", "-o"])
+        .arg(path)
+        .stdin(Stdio::piped())
+        .spawn()
+        .or_io_error("failed to execute zstd, install by 'brew install zstd'")
+}
+
+// read decompressed content from stdout of returned process
+pub fn decompress(path: &Path) -> Result<Child> {
+    Command::new("zstd")
+        .args(["-q", "-d", "-c"])
+        .arg(path)
+        .stdout(Stdio::piped())
+        .spawn()
+        .or_io_error("failed to execute zstd, install by 'brew install zstd'")
+}
+
+pub fn wait(mut child: Child) -> Result<()> {
+    let status = child.wait().or_io_error("failed to wait zstd")?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(VzError::Io("failed to execute zstd".to_owned(), io::Error::other(format!("exit status={status}"))))
+    }
+}
@@ -1,5 +1,4 @@
 use std::process;
-use std::time::Duration;
 
 use block2::StackBlock;
 use dispatch2::DispatchQueue;
@@ -13,10 +12,13 @@ use tracing::error;
 use tracing::info;
 use tracing::info_span;
 
+use crate::config::global_config;
+
 pub mod gui_delegate;
 pub mod linux;
 pub mod mac_os;
 pub mod mac_os_installer;
+pub mod port_forward;
 pub mod vm_delegate;
 
 pub fn start_vm(vm: &MainThreadBound<Retained<VZVirtualMachine>>) {
@@ -43,7 +45,7 @@ pub fn stop_vm(name: &str, vm: &MainThreadBound<Retained<VZVirtualMachine>>) {
         let _enter = span.enter();
         info!("stop vm");
         if request_stop_vm(vm.get(marker)) {
-            let timeout = DispatchTime::try_from(Duration::from_secs(15)).unwrap();
+            let timeout = DispatchTime::try_from(global_config::get().stop_timeout()).unwrap();
             let result = DispatchQueue::main().after(timeout, || force_stop_vm(vm));
             if let Err(err) = result {
                 error!("failed to queue force_stop_vm, err={err:?}");
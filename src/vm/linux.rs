@@ -33,16 +33,18 @@ use tracing::info;
 use crate::config::vm_config;
 use crate::config::vm_config::VmConfig;
 use crate::config::vm_dir::VmDir;
+use crate::error::Result;
+use crate::error::VzError;
 use crate::util::path::PathExtension as _;
 
-pub fn create_vm(dir: &VmDir, config: &VmConfig, gui: bool, mount: Option<&PathBuf>) -> Retained<VZVirtualMachine> {
+pub fn create_vm(dir: &VmDir, config: &VmConfig, gui: bool, mounts: &[PathBuf]) -> Result<Retained<VZVirtualMachine>> {
     info!("create linux vm");
-    let vz_config = create_vm_config(dir, config, gui, mount);
+    let vz_config = create_vm_config(dir, config, gui, mounts)?;
     unsafe {
-        vz_config.validateWithError().unwrap_or_else(|err| {
-            panic!("virtual machine config validation error, err={}", err.localizedDescription())
-        });
-        VZVirtualMachine::initWithConfiguration(VZVirtualMachine::alloc(), &vz_config)
+        vz_config.validateWithError().map_err(|err| {
+            VzError::Framework(format!("virtual machine config validation error, err={}", err.localizedDescription()))
+        })?;
+        Ok(VZVirtualMachine::initWithConfiguration(VZVirtualMachine::alloc(), &vz_config))
     }
 }
 
@@ -50,8 +52,8 @@ fn create_vm_config(
     dir: &VmDir,
     config: &VmConfig,
     gui: bool,
-    mount: Option<&PathBuf>,
-) -> Retained<VZVirtualMachineConfiguration> {
+    mounts: &[PathBuf],
+) -> Result<Retained<VZVirtualMachineConfiguration>> {
     unsafe {
         let vz_config = VZVirtualMachineConfiguration::new();
         vz_config.setCPUCount(config.cpu);
@@ -70,8 +72,8 @@ fn create_vm_config(
             )]));
         }
 
-        vz_config.setNetworkDevices(&NSArray::from_retained_slice(&[vm_config::network(config)]));
-        vz_config.setStorageDevices(&NSArray::from_retained_slice(&storage(dir, mount)));
+        vz_config.setNetworkDevices(&NSArray::from_retained_slice(&[vm_config::network(config)?]));
+        vz_config.setStorageDevices(&NSArray::from_retained_slice(&storage(dir, mounts)?));
 
         vz_config.setMemoryBalloonDevices(&NSArray::from_retained_slice(&[Retained::into_super(
             VZVirtioTraditionalMemoryBalloonDeviceConfiguration::new(),
@@ -81,7 +83,7 @@ fn create_vm_config(
         )]));
 
         let mut sharings: Vec<Retained<VZDirectorySharingDeviceConfiguration>> = vec![];
-        if let Some(sharing) = vm_config::sharing_directories(config) {
+        if let Some(sharing) = vm_config::sharing_directories(config)? {
             sharings.push(sharing);
         }
         if let Some(true) = config.rosetta {
@@ -94,7 +96,7 @@ fn create_vm_config(
         }
         vz_config.setDirectorySharingDevices(&NSArray::from_retained_slice(&sharings));
 
-        vz_config
+        Ok(vz_config)
     }
 }
 
@@ -107,50 +109,54 @@ fn boot_loader(dir: &VmDir) -> Retained<VZEFIBootLoader> {
     }
 }
 
-fn storage(dir: &VmDir, mount: Option<&PathBuf>) -> Vec<Retained<VZStorageDeviceConfiguration>> {
-    let disk = disk(&dir.disk_path);
-    let mut storage = vec![disk];
-    if let Option::Some(mount) = mount {
-        let mounted_disk = mount_disk(mount);
-        storage.push(mounted_disk);
+fn storage(dir: &VmDir, mounts: &[PathBuf]) -> Result<Vec<Retained<VZStorageDeviceConfiguration>>> {
+    let mut storage = vec![disk(&dir.disk_path, false)?];
+    if dir.seed_path.exists() {
+        info!("attach cloud-init seed, path={}", dir.seed_path.to_string_lossy());
+        storage.push(disk(&dir.seed_path, true)?);
     }
-    storage
+    for mount in mounts {
+        storage.push(mount_disk(mount)?);
+    }
+    Ok(storage)
 }
 
-fn disk(disk: &Path) -> Retained<VZStorageDeviceConfiguration> {
+fn disk(disk: &Path, read_only: bool) -> Result<Retained<VZStorageDeviceConfiguration>> {
     let url = NSURL::initFileURLWithPath(NSURL::alloc(), &NSString::from_str(&disk.to_string_lossy()));
     unsafe {
         let attachment =
             VZDiskImageStorageDeviceAttachment::initWithURL_readOnly_cachingMode_synchronizationMode_error(
                 VZDiskImageStorageDeviceAttachment::alloc(),
                 &url,
-                false,
+                read_only,
                 VZDiskImageCachingMode::Automatic,
                 VZDiskImageSynchronizationMode::Fsync,
             )
-            .unwrap_or_else(|err| panic!("failed to create disk, err={}", err.localizedDescription()));
+            .map_err(|err| VzError::Framework(format!("failed to create disk, err={}", err.localizedDescription())))?;
         let block_device = VZVirtioBlockDeviceConfiguration::initWithAttachment(
             VZVirtioBlockDeviceConfiguration::alloc(),
             &attachment,
         );
-        Retained::into_super(block_device)
+        Ok(Retained::into_super(block_device))
     }
 }
 
-fn mount_disk(mount: &Path) -> Retained<VZStorageDeviceConfiguration> {
+fn mount_disk(mount: &Path) -> Result<Retained<VZStorageDeviceConfiguration>> {
     unsafe {
         let attachment = VZDiskImageStorageDeviceAttachment::initWithURL_readOnly_error(
             VZDiskImageStorageDeviceAttachment::alloc(),
             &mount.to_ns_url(),
             true,
         )
-        .unwrap_or_else(|err| panic!("failed to create mount disk, err={}", err.localizedDescription()));
+        .map_err(|err| {
+            VzError::Framework(format!("failed to create mount disk, err={}", err.localizedDescription()))
+        })?;
 
         let disk = VZUSBMassStorageDeviceConfiguration::initWithAttachment(
             VZUSBMassStorageDeviceConfiguration::alloc(),
             &attachment,
         );
-        Retained::into_super(disk)
+        Ok(Retained::into_super(disk))
     }
 }
 
@@ -38,16 +38,18 @@ use tracing::info;
 use crate::config::vm_config;
 use crate::config::vm_config::VmConfig;
 use crate::config::vm_dir::VmDir;
+use crate::error::Result;
+use crate::error::VzError;
 use crate::util::path::PathExtension as _;
 
-pub fn create_vm(dir: &VmDir, config: &VmConfig, marker: MainThreadMarker) -> Retained<VZVirtualMachine> {
+pub fn create_vm(dir: &VmDir, config: &VmConfig, marker: MainThreadMarker) -> Result<Retained<VZVirtualMachine>> {
     info!("create macOS vm");
-    let vz_config = create_vm_config(dir, config, marker);
+    let vz_config = create_vm_config(dir, config, marker)?;
     unsafe {
-        vz_config.validateWithError().unwrap_or_else(|err| {
-            panic!("virtual machine config validation error, err={}", err.localizedDescription())
-        });
-        VZVirtualMachine::initWithConfiguration(VZVirtualMachine::alloc(), &vz_config)
+        vz_config.validateWithError().map_err(|err| {
+            VzError::Framework(format!("virtual machine config validation error, err={}", err.localizedDescription()))
+        })?;
+        Ok(VZVirtualMachine::initWithConfiguration(VZVirtualMachine::alloc(), &vz_config))
     }
 }
 
@@ -68,14 +70,15 @@ fn create_vm_config(
     dir: &VmDir,
     config: &VmConfig,
     marker: MainThreadMarker,
-) -> Retained<VZVirtualMachineConfiguration> {
+) -> Result<Retained<VZVirtualMachineConfiguration>> {
     unsafe {
         let vz_config = VZVirtualMachineConfiguration::new();
         vz_config.setCPUCount(config.cpu);
         vz_config.setMemorySize(config.ram);
 
         vz_config.setBootLoader(Some(&VZMacOSBootLoader::new()));
-        vz_config.setPlatform(&platform(dir, config));
+        let platform = platform(dir, config)?;
+        vz_config.setPlatform(&platform);
 
         vz_config.setGraphicsDevices(&NSArray::from_retained_slice(&[display(1920, 1080, marker)]));
         vz_config.setAudioDevices(&NSArray::from_retained_slice(&[audio()]));
@@ -85,8 +88,8 @@ fn create_vm_config(
             VZMacTrackpadConfiguration::new(),
         )]));
 
-        vz_config.setNetworkDevices(&NSArray::from_retained_slice(&[vm_config::network(config)]));
-        vz_config.setStorageDevices(&NSArray::from_retained_slice(&[disk(&dir.disk_path)]));
+        vz_config.setNetworkDevices(&NSArray::from_retained_slice(&[vm_config::network(config)?]));
+        vz_config.setStorageDevices(&NSArray::from_retained_slice(&[disk(&dir.disk_path)?]));
 
         vz_config.setMemoryBalloonDevices(&NSArray::from_retained_slice(&[Retained::into_super(
             VZVirtioTraditionalMemoryBalloonDeviceConfiguration::new(),
@@ -95,10 +98,10 @@ fn create_vm_config(
             VZVirtioEntropyDeviceConfiguration::new(),
         )]));
 
-        if let Some(sharing) = vm_config::sharing_directories(config) {
+        if let Some(sharing) = vm_config::sharing_directories(config)? {
             vz_config.setDirectorySharingDevices(&NSArray::from_retained_slice(&[sharing]));
         }
-        vz_config
+        Ok(vz_config)
     }
 }
 
@@ -112,20 +115,28 @@ fn audio() -> Retained<VZAudioDeviceConfiguration> {
     }
 }
 
-fn platform(dir: &VmDir, config: &VmConfig) -> Retained<VZPlatformConfiguration> {
+fn platform(dir: &VmDir, config: &VmConfig) -> Result<Retained<VZPlatformConfiguration>> {
+    let hardware_model = config
+        .hardware_model
+        .as_ref()
+        .ok_or_else(|| VzError::InvalidConfig("hardware_model is required for macOS vm".to_owned()))?;
+    let machine_identifier = config
+        .machine_identifier
+        .as_ref()
+        .ok_or_else(|| VzError::InvalidConfig("machine_identifier is required for macOS vm".to_owned()))?;
     unsafe {
         let platform = VZMacPlatformConfiguration::new();
         platform.setAuxiliaryStorage(Some(&VZMacAuxiliaryStorage::initWithURL(
             VZMacAuxiliaryStorage::alloc(),
             &dir.nvram_path.to_ns_url(),
         )));
-        platform.setHardwareModel(&hardware_model(config.hardware_model.as_ref().unwrap()));
-        platform.setMachineIdentifier(&machine_identifier(config.machine_identifier.as_ref().unwrap()));
-        Retained::into_super(platform)
+        platform.setHardwareModel(&self::hardware_model(hardware_model));
+        platform.setMachineIdentifier(&self::machine_identifier(machine_identifier));
+        Ok(Retained::into_super(platform))
     }
 }
 
-fn disk(disk: &Path) -> Retained<VZStorageDeviceConfiguration> {
+fn disk(disk: &Path) -> Result<Retained<VZStorageDeviceConfiguration>> {
     unsafe {
         let attachment =
             VZDiskImageStorageDeviceAttachment::initWithURL_readOnly_cachingMode_synchronizationMode_error(
@@ -135,12 +146,12 @@ fn disk(disk: &Path) -> Retained<VZStorageDeviceConfiguration> {
                 VZDiskImageCachingMode::Automatic,
                 VZDiskImageSynchronizationMode::Fsync,
             )
-            .unwrap_or_else(|err| panic!("failed to create disk, err={}", err.localizedDescription()));
+            .map_err(|err| VzError::Framework(format!("failed to create disk, err={}", err.localizedDescription())))?;
         let block_device = VZVirtioBlockDeviceConfiguration::initWithAttachment(
             VZVirtioBlockDeviceConfiguration::alloc(),
             &attachment,
         );
-        Retained::into_super(block_device)
+        Ok(Retained::into_super(block_device))
     }
 }
 
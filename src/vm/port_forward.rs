@@ -0,0 +1,68 @@
+use std::fs;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::SystemTime;
+
+use tracing::info;
+
+use crate::config::global_config;
+use crate::config::vm_config::PortForward;
+use crate::config::vm_config::VmConfig;
+use crate::error::Result;
+use crate::error::VzError;
+use crate::util::guest_ip;
+use crate::util::tcp_proxy;
+use crate::util::tcp_proxy::Target;
+
+// listen on localhost only, guest ip is cached, and resolved again if lease file changed or connect failed
+pub fn start(config: &VmConfig) -> Result<()> {
+    for port in &config.ports {
+        let forward = PortForward::parse(port)
+            .ok_or_else(|| VzError::InvalidConfig(format!("invalid port forwarding, value={port}")))?;
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, forward.host_port));
+        let guest = Guest {
+            mac_address: config.mac_address.clone(),
+            port: forward.guest_port,
+            dhcpd_leases: global_config::get().dhcpd_leases(),
+            cache: Mutex::new(None),
+        };
+        tcp_proxy::start(address, guest)?;
+        info!("forward port, host={address}, guest_port={}", forward.guest_port);
+    }
+    Ok(())
+}
+
+struct Guest {
+    mac_address: String,
+    port: u16,
+    dhcpd_leases: PathBuf,
+    cache: Mutex<Option<CachedIp>>,
+}
+
+struct CachedIp {
+    ip: IpAddr,
+    leases_modified: Option<SystemTime>,
+}
+
+impl Target for Guest {
+    fn resolve(&self) -> Option<SocketAddr> {
+        let leases_modified = fs::metadata(&self.dhcpd_leases).and_then(|metadata| metadata.modified()).ok();
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(cached) = cache.as_ref()
+            && cached.leases_modified == leases_modified
+        {
+            return Some(SocketAddr::new(cached.ip, self.port));
+        }
+        let ip: IpAddr = guest_ip::ip_addrs(&self.dhcpd_leases).ok()?.remove(&self.mac_address)?.parse().ok()?;
+        *cache = Some(CachedIp { ip, leases_modified });
+        Some(SocketAddr::new(ip, self.port))
+    }
+
+    fn invalidate(&self) {
+        *self.cache.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+}
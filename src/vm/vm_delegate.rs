@@ -1,6 +1,9 @@
+use std::fs;
+use std::path::PathBuf;
 use std::process;
 
 use objc2::AllocAnyThread as _;
+use objc2::DeclaredClass as _;
 use objc2::define_class;
 use objc2::msg_send;
 use objc2::rc::Retained;
@@ -12,10 +15,19 @@ use objc2_virtualization::VZVirtualMachine;
 use objc2_virtualization::VZVirtualMachineDelegate;
 use tracing::error;
 use tracing::info;
+use tracing::warn;
+
+use crate::config::hosts;
+
+pub struct Ivars {
+    // cloud-init seed is only needed for first boot
+    seed_path: Option<PathBuf>,
+}
 
 define_class!(
     #[unsafe(super = NSObject)]
     #[name = "VmDelegate"]
+    #[ivars = Ivars]
     pub struct VmDelegate;
 
     unsafe impl NSObjectProtocol for VmDelegate {}
@@ -24,6 +36,14 @@ define_class!(
         #[unsafe(method(guestDidStopVirtualMachine:))]
         fn guest_did_stop_virtual_machine(&self, _: &VZVirtualMachine) {
             info!("guest has stopped the vm");
+            if let Some(seed_path) = &self.ivars().seed_path {
+                info!("remove cloud-init seed after first boot, path={}", seed_path.to_string_lossy());
+                if let Err(err) = fs::remove_file(seed_path) {
+                    warn!("failed to remove cloud-init seed, err={err}");
+                }
+            }
+            // pid() doesn't see lock of own process, so this vm is removed from hosts file, releasing lock is fine as process exits
+            hosts::update_hosts(None);
             process::exit(0);
         }
 
@@ -47,7 +67,8 @@ define_class!(
 );
 
 impl VmDelegate {
-    pub fn new() -> Retained<Self> {
-        unsafe { msg_send![VmDelegate::alloc(), init] }
+    pub fn new(seed_path: Option<PathBuf>) -> Retained<Self> {
+        let this = VmDelegate::alloc().set_ivars(Ivars { seed_path });
+        unsafe { msg_send![super(this), init] }
     }
 }